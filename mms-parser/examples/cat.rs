@@ -12,11 +12,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let data = read_file(&path.into()).unwrap();
 
-    let (remainder, parsed) = nom::dbg_dmp(parse_wap_push, "something")(&data).unwrap();
-    println!("Raminder length: {:?}", remainder.len());
+    let parsed = parse_wap_push(&data)?;
     println!("Notification Headers: {:#?}", parsed);
 
-    let body = parsed.parse_body().unwrap();
+    let body = parsed.parse_body()?;
     println!("Message Headers: {:#?}", body);
 
     Ok(())
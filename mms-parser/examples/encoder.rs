@@ -1,8 +1,8 @@
 use mms_parser::{
     encoder::encode_mms_message,
     types::{
-        mms_header::{MessageTypeField, MmsHeader, MmsHeaderValue},
-        VndWapMmsMessage,
+        mms_header::{FromField, MessageTypeField, MmsHeader, MmsHeaderValue},
+        Address, VndWapMmsMessage,
     },
 };
 
@@ -59,17 +59,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     message
         .headers
-        .insert(MmsHeader::To, format!("+{}/TYPE=PLMN", to).into());
+        .insert(MmsHeader::To, Address::Plmn(format!("+{}", to)).into());
 
-    message
-        .headers
-        .insert(MmsHeader::From, format!("+{}/TYPE=PLMN", from).into());
+    message.headers.insert(
+        MmsHeader::From,
+        FromField::Address(Address::Plmn(format!("+{}", from))).into(),
+    );
 
     let mut file = File::open(body_path)?;
-    file.read_to_end(&mut message.body)?;
+    file.read_to_end(message.body.to_mut())?;
     // println!("Generated Messag: {:#?}", message);
 
-    let encoded = encode_mms_message(message.headers, (mime_type, message.body));
+    let encoded = encode_mms_message(
+        message.headers,
+        (mime_type, message.body.into_owned()),
+    )?;
 
     let mut out = File::create(save)?;
     out.write_all(&encoded)?;
@@ -13,14 +13,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let data = read_file(&path.into()).unwrap();
 
     let body_data = if is_wap {
-        let (_, wap) = nom::dbg_dmp(parse_wap_push, "something")(&data).unwrap();
+        let wap = parse_wap_push(&data)?;
         // println!("Notification Headers: {:#?}", wap);
-        wap.data
+        wap.data.into_owned()
     } else {
         data
     };
 
-    let (_, split) = split_header_fields(&*body_data).unwrap();
+    let split = split_header_fields(&*body_data)?;
 
     match include_errors {
         true => {
@@ -0,0 +1,184 @@
+use crate::types::charset_codes::CHARSET_CODES;
+
+use encoding::{
+    all::{ASCII, ISO_8859_1, UTF_16BE, UTF_16LE},
+    label::encoding_from_whatwg_label,
+    DecoderTrap, EncoderTrap, EncodingRef,
+};
+use std::str::FromStr;
+
+/// A character set text can be encoded in, identified by it's MIBenum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Charset(u64);
+
+impl Charset {
+    pub const US_ASCII: Self = Self(3);
+    pub const UTF_8: Self = Self(106);
+    pub const UTF_16: Self = Self(1015);
+
+    /// Only charsets this crate knows how to encode are returned
+    pub fn from_mib_enum(code: u64) -> Option<Self> {
+        charset_name(code).map(|_| Self(code))
+    }
+
+    pub fn mib_enum(self) -> u64 {
+        self.0
+    }
+
+    /// The IANA name of the charset
+    pub fn name(self) -> &'static str {
+        charset_name(self.0).unwrap_or("UTF-8")
+    }
+
+    pub(crate) fn is_utf_16(self) -> bool {
+        is_utf_16(self.0)
+    }
+
+    /// Encode `text` without a null terminator, UTF-16 is written big endian
+    /// with a byte order mark
+    pub(crate) fn encode(self, text: &str) -> Result<Vec<u8>, &'static str> {
+        // The WHATWG labels for US-ASCII and ISO-8859-1 are windows-1252, which
+        // would encode characters those charsets don't have
+        let encoding: EncodingRef = match self.0 {
+            3 => ASCII,
+            4 => ISO_8859_1,
+            1014 => UTF_16LE,
+            1000 | 1013 | 1015 => UTF_16BE,
+            code => charset_name(code)
+                .and_then(encoding_from_whatwg_label)
+                .ok_or("Unsupported charset")?,
+        };
+
+        let mut buf = if self.0 == 1015 {
+            vec![0xFE, 0xFF]
+        } else {
+            Vec::new()
+        };
+        buf.append(
+            &mut encoding
+                .encode(text, EncoderTrap::Strict)
+                .map_err(|_| "Text can't be represented in the charset")?,
+        );
+
+        Ok(buf)
+    }
+}
+
+impl FromStr for Charset {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        charset_code(name).map(Self).ok_or("Unknown charset")
+    }
+}
+
+/// The name of the charset with the MIBenum `code`
+pub(crate) fn charset_name(code: u64) -> Option<&'static str> {
+    CHARSET_CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, name)| *name)
+}
+
+/// The MIBenum of the charset called `name`, ignoring case
+pub(crate) fn charset_code(name: &str) -> Option<u64> {
+    CHARSET_CODES
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(code, _)| *code)
+}
+
+fn is_utf_16(code: u64) -> bool {
+    matches!(code, 1000 | 1013 | 1014 | 1015)
+}
+
+/// Pick the decoder for UTF-16 text, preferring the byte order mark if there
+/// is one. Without one the spec says big endian should be assumed, except
+/// for UTF-16LE
+fn utf_16_encoding(code: u64, data: &[u8]) -> (EncodingRef, &[u8]) {
+    match data {
+        [0xFE, 0xFF, rest @ ..] => (UTF_16BE, rest),
+        [0xFF, 0xFE, rest @ ..] => (UTF_16LE, rest),
+        _ if code == 1014 => (UTF_16LE, data),
+        _ => (UTF_16BE, data),
+    }
+}
+
+/// Decode a Text-string in the charset with the MIBenum `code`, `data` may
+/// still include the null terminator
+pub(crate) fn decode(code: u64, data: &[u8]) -> Result<String, String> {
+    let (encoding, data) = if is_utf_16(code) {
+        // The terminator is a single null byte, which can leave an odd number
+        // of bytes, but some phones terminate with a null character instead
+        let data = match data.len() % 2 {
+            1 if data.last() == Some(&0) => &data[..data.len() - 1],
+            _ => data,
+        };
+        utf_16_encoding(code, data)
+    } else {
+        let data = match data {
+            [d @ .., 0] => d,
+            d => d,
+        };
+        let data = match data {
+            [0x7F, d @ ..] if matches!(d.first(), Some(128..=255)) => d,
+            d => d,
+        };
+
+        let encoding = charset_name(code)
+            .and_then(encoding_from_whatwg_label)
+            .ok_or_else(|| format!("Unsupported charset {}", code))?;
+        (encoding, data)
+    };
+
+    let decoded = encoding
+        .decode(data, DecoderTrap::Replace)
+        .map_err(|e| format!("Invalid {} text: {}", encoding.name(), e))?;
+
+    Ok(decoded.trim_end_matches('\0').to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf_16_with_bom() {
+        let data = [0xFF, 0xFE, b'h', 0, b'i', 0, 0];
+        assert_eq!(decode(1015, &data).unwrap(), "hi");
+    }
+
+    #[test]
+    fn utf_16_without_bom_is_big_endian() {
+        let data = [0x00, b'h', 0x04, 0x10, 0];
+        assert_eq!(decode(1015, &data).unwrap(), "h\u{410}");
+    }
+
+    #[test]
+    fn shift_jis() {
+        let data = [0x82, 0xA0, 0];
+        assert_eq!(decode(17, &data).unwrap(), "\u{3042}");
+    }
+
+    #[test]
+    fn utf_16_round_trip() {
+        let mut data = Charset::UTF_16.encode("h\u{410}").unwrap();
+        assert_eq!(data, [0xFE, 0xFF, 0x00, b'h', 0x04, 0x10]);
+
+        data.push(0);
+        assert_eq!(decode(1015, &data).unwrap(), "h\u{410}");
+    }
+
+    #[test]
+    fn unrepresentable_text() {
+        Charset::US_ASCII.encode("\u{410}").unwrap_err();
+        Charset::US_ASCII.encode("caf\u{E9}").unwrap_err();
+        Charset(4).encode("\u{20AC}").unwrap_err();
+        assert_eq!(Charset(4).encode("caf\u{E9}").unwrap(), b"caf\xE9");
+    }
+
+    #[test]
+    fn unknown_charset() {
+        decode(9999, b"abc\0").unwrap_err();
+    }
+}
@@ -7,23 +7,95 @@ use multipart::EncodableBody;
 
 use crate::{
     types::{
-        mms_header::{self as mms_header_types, MmsHeader, MmsHeaderValue},
-        MessageHeader,
+        mms_header::{
+            self as mms_header_types, MMFlagsField, MMStateField,
+            MessageTypeField, MmsHeader, MmsHeaderValue, ReadStatusField,
+            StatusField, YesNoField,
+        },
+        Address, MessageHeader, VndWapMmsMessage,
     },
-    MultiMap,
+    Charset, MultiMap,
 };
 
 use std::{
+    collections::HashMap,
     convert::TryFrom,
+    error::Error,
+    fmt,
     fs::File,
-    io::Read,
+    io::{self, Read},
     ops::{Deref, DerefMut},
-    path::Path,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+#[derive(Debug)]
+pub enum EncodeError {
+    HeaderMsg((&'static str, MmsHeader)),
+    Msg(&'static str),
+    /// A single valued header was given more than once, this includes a
+    /// Content-Type in the headers of a message with a body
+    DuplicateHeader(MmsHeader),
+    /// The content type of a file couldn't be determined from it's extension
+    UnknownContentType(PathBuf),
+    /// The path of a file doesn't end in a file name, so it can't be used to
+    /// name a part
+    NoFileName(PathBuf),
+    Io(io::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::HeaderMsg((msg, field)) => {
+                write!(f, "{} on field {:?}", msg, field)
+            }
+            Self::Msg(msg) => write!(f, "{}", msg),
+            Self::DuplicateHeader(field) => {
+                write!(f, "Header {:?} can only appear once", field)
+            }
+            Self::UnknownContentType(file) => {
+                write!(f, "Couldn't determine content type for file {:?}", file)
+            }
+            Self::NoFileName(file) => {
+                write!(f, "Path {:?} doesn't have a file name", file)
+            }
+            Self::Io(_) => write!(f, "IO error"),
+        }
+    }
+}
+
+impl Error for EncodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::convert::From<(&'static str, MmsHeader)> for EncodeError {
+    fn from(e: (&'static str, MmsHeader)) -> Self {
+        Self::HeaderMsg(e)
+    }
+}
+
+impl std::convert::From<&'static str> for EncodeError {
+    fn from(e: &'static str) -> Self {
+        Self::Msg(e)
+    }
+}
+
+impl std::convert::From<io::Error> for EncodeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 pub struct MSendReq {
     headers: MultiMap<MmsHeader, MmsHeaderValue>,
     body: multipart::EncoderBuilder<multipart::RelatedBodyPart>,
+    charset: Option<Charset>,
 }
 
 impl MSendReq {
@@ -31,43 +103,24 @@ impl MSendReq {
         Self {
             headers: MultiMap::new(),
             body: multipart::EncoderBuilder::new(),
+            charset: None,
         }
     }
     fn finalize_headers(&mut self) {
-        use mms_header_types::MessageTypeField;
         use MmsHeader::*;
 
-        let mut headers = vec![
-            (XMmsMessageType, MessageTypeField::MSendReq.into()),
-            (
+        self.headers
+            .insert(XMmsMessageType, MessageTypeField::MSendReq.into());
+        if !self.headers.contains_key(&XMmsTransactionId) {
+            // TODO: This may be dangerous behaviour, there is nowhere the id
+            // can be easily returned
+            self.headers.insert(
                 XMmsTransactionId,
-                self.headers
-                    .remove(&XMmsTransactionId)
-                    // TODO: This may be dangerous behaviour, there is nowhere the id can be
-                    // easily returned
-                    .unwrap_or(uuid::Uuid::new_v4().to_string().into()),
-            ),
-            (XMmsMMSVersion, crate::MMS_VERSION.into()),
-        ];
-
-        // TODO: This should check for dupliate headers, espicilly of the headers listed above
-        let mut user_headers: Vec<(MmsHeader, MmsHeaderValue)> = self
-            .headers
-            .drain_pairs()
-            .map(|(key, values)| {
-                // TODO: Only include specifc keys and limit most of them to
-                // only one value per key
-                values
-                    .map(|v| (key.clone(), v))
-                    .collect::<Vec<(MmsHeader, MmsHeaderValue)>>()
-            })
-            .flatten()
-            .collect();
-
-        headers.append(&mut user_headers);
-        let headers: MultiMap<_, _> = headers.drain(..).collect();
-
-        self.headers = headers;
+                uuid::Uuid::new_v4().to_string().into(),
+            );
+        }
+        self.headers
+            .insert(XMmsMMSVersion, crate::MMS_VERSION.into());
     }
     // TODO: Most of these functions should return an error if there is already a value set
     pub fn transaction_id(&mut self, id: String) {
@@ -76,48 +129,90 @@ impl MSendReq {
     pub fn from(&mut self, addr: mms_header_types::FromField) {
         self.insert(MmsHeader::From, addr.into());
     }
-    // TODO: Replace Strings in to, cc and bcc with an address enum of some sort
-    pub fn to(&mut self, addr: String) {
-        self.insert(MmsHeader::To, addr.into());
+    /// Add a recipient, this can be called more than once to send the
+    /// message to several recipients
+    pub fn to(&mut self, addr: Address) {
+        self.append(MmsHeader::To, addr.into());
     }
-    pub fn cc(&mut self, addr: String) {
-        self.insert(MmsHeader::Cc, addr.into());
+    pub fn cc(&mut self, addr: Address) {
+        self.append(MmsHeader::Cc, addr.into());
     }
-    pub fn bcc(&mut self, addr: String) {
-        self.insert(MmsHeader::Bcc, addr.into());
+    pub fn bcc(&mut self, addr: Address) {
+        self.append(MmsHeader::Bcc, addr.into());
     }
     pub fn subject(&mut self, subject: String) {
         self.insert(MmsHeader::Subject, subject.into());
     }
+    /// Charset to use for the subject and text parts, by default ascii text
+    /// is sent without a charset and anything else is sent as UTF-8. Text
+    /// which can't be represented in `charset` is also sent as UTF-8
+    pub fn charset(&mut self, charset: Charset) {
+        self.charset = Some(charset);
+    }
     pub fn class(&mut self, class: mms_header_types::ClassIdentifier) {
         self.insert(MmsHeader::XMmsMessageClass, class.into());
     }
     pub fn delivery_report(&mut self, report: bool) {
-        self.insert(MmsHeader::XMmsDeliveryReport, report.into());
+        self.insert(
+            MmsHeader::XMmsDeliveryReport,
+            YesNoField::from(report).into(),
+        );
     }
     pub fn read_report(&mut self, report: bool) {
-        self.insert(MmsHeader::XMmsReadReport, report.into());
+        self.insert(MmsHeader::XMmsReadReport, YesNoField::from(report).into());
     }
     // TODO: Proper error handling
     pub fn body_part(&mut self, part: multipart::RelatedBodyPart) {
         self.body.part(part)
     }
-    pub fn body_file<P: AsRef<Path>>(&mut self, file: P) {
+    pub fn body_text(&mut self, text: &str) {
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let item = multipart::RelatedBodyPart::text(
+            text,
+            self.charset,
+            format!("<{}>", id),
+            format!("{}.txt", id),
+        );
+
+        self.body_part(item)
+    }
+    /// Add a file as a part, it's content type is guessed from the file's
+    /// extension
+    pub fn body_file<P: AsRef<Path>>(
+        &mut self,
+        file: P,
+    ) -> Result<(), EncodeError> {
         let file = file.as_ref();
-        let mut mime = mime_from_file(file).to_string();
+        let no_name = || EncodeError::NoFileName(file.to_path_buf());
+        let id = file_id(file).ok_or_else(no_name)?;
+        let location = file_name(file).ok_or_else(no_name)?;
+        let mime = mime_from_file(file)?;
 
         let data = {
-            let mut file = File::open(file).unwrap();
+            let mut file = File::open(file)?;
             let mut buffer: Vec<u8> = Vec::new();
 
-            file.read_to_end(&mut buffer).unwrap();
+            file.read_to_end(&mut buffer)?;
             buffer
         };
-        let id = file_id(&file);
-        let location = file_name(&file);
 
+        // Text files are assumed to be UTF-8, the charset is only set when
+        // they're valid UTF-8 so it can't be wrong
+        let charset = match mime.get_param(mime::CHARSET) {
+            None if mime.type_() == mime::TEXT
+                && std::str::from_utf8(&data).is_ok() =>
+            {
+                "; charset=utf-8"
+            }
+            _ => "",
+        };
+        let mut mime = mime.to_string();
+        mime.push_str(charset);
         mime.push_str(&*format!("; name=\"{}\"", location));
-        let mime = mime.parse().unwrap();
+        let mime = mime
+            .parse()
+            .map_err(|_| EncodeError::Msg("Invalid file name"))?;
 
         let item = multipart::RelatedBodyPart::new(
             mime,
@@ -126,55 +221,43 @@ impl MSendReq {
             location.to_string(),
         );
 
-        self.body_part(item)
+        self.body_part(item);
+        Ok(())
     }
-    pub fn encode(mut self) -> Vec<u8> {
+    pub fn encode(mut self) -> Result<Vec<u8>, EncodeError> {
         self.finalize_headers();
-        let complete_body = self.body.build().unwrap();
-        encode_mms_message(self.headers, complete_body)
+        let complete_body = self.body.build()?;
+        encode_message(self.headers, complete_body, self.charset)
     }
 }
 
-fn mime_from_file<P: AsRef<Path>>(file: P) -> mime::Mime {
+fn mime_from_file<P: AsRef<Path>>(file: P) -> Result<mime::Mime, EncodeError> {
     let file = file.as_ref();
-    let error_message =
-        |file| format!("Couldn't determine content type for file {:?}", file);
+    let unknown = || EncodeError::UnknownContentType(file.to_path_buf());
 
     let extension: &str = file
         .extension()
-        .expect(&*error_message(file))
-        .to_str()
-        .expect(&*error_message(file));
+        .and_then(|e| e.to_str())
+        .ok_or_else(unknown)?;
 
     // TODO: This is really hacky because I get the wrong content type back from mime_db
     if extension == "smil" {
-        "application/smil".parse().unwrap()
+        Ok("application/smil".parse().unwrap())
     } else {
         mime_db::lookup(extension)
-            .expect(&*error_message(file))
-            .parse()
-            .expect(&*error_message(file))
+            .and_then(|m| m.parse().ok())
+            .ok_or_else(unknown)
     }
 }
 
-fn file_id<P: AsRef<Path>>(file: P) -> String {
-    let id = file
-        .as_ref()
-        .file_stem()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
-    id
+fn file_id<P: AsRef<Path>>(file: P) -> Option<String> {
+    let id = file.as_ref().file_stem()?.to_string_lossy().to_string();
+    Some(id)
 }
 
-fn file_name<P: AsRef<Path>>(file: P) -> String {
-    let location = file
-        .as_ref()
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
-    location
+fn file_name<P: AsRef<Path>>(file: P) -> Option<String> {
+    let location = file.as_ref().file_name()?.to_string_lossy().to_string();
+    Some(location)
 }
 
 impl Deref for MSendReq {
@@ -191,63 +274,583 @@ impl DerefMut for MSendReq {
     }
 }
 
+/// Headers which start every pdu sent by the client
+fn pdu_headers(
+    message_type: MessageTypeField,
+    transaction_id: Option<String>,
+) -> MultiMap<MmsHeader, MmsHeaderValue> {
+    let mut headers = MultiMap::new();
+    headers.insert(MmsHeader::XMmsMessageType, message_type.into());
+    if let Some(id) = transaction_id {
+        headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    headers.insert(MmsHeader::XMmsMMSVersion, crate::MMS_VERSION.into());
+    headers
+}
+
+/// M-NotifyResp.ind, tells the MMSC what the client did with a
+/// M-Notification.ind
+pub struct MNotifyRespInd {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MNotifyRespInd {
+    /// `transaction_id` is the id of the M-Notification.ind, and `status` is
+    /// usually Retrieved, Deferred, or Rejected
+    pub fn new(transaction_id: String, status: StatusField) -> Self {
+        let mut headers =
+            pdu_headers(MessageTypeField::MNotifyrespInd, Some(transaction_id));
+        headers.insert(MmsHeader::XMmsStatus, status.into());
+        Self { headers }
+    }
+    /// Whether the sender may be sent a delivery report
+    pub fn report_allowed(&mut self, allowed: bool) {
+        self.headers
+            .insert(MmsHeader::XMmsReportAllowed, allowed.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// M-Acknowledge.ind, tells the MMSC a message which was fetched later
+/// was received
+pub struct MAcknowledgeInd {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MAcknowledgeInd {
+    /// `transaction_id` is the id of the M-Retrieve.conf
+    pub fn new(transaction_id: String) -> Self {
+        let headers = pdu_headers(
+            MessageTypeField::MAcknowledgeInd,
+            Some(transaction_id),
+        );
+        Self { headers }
+    }
+    /// Whether the sender may be sent a delivery report
+    pub fn report_allowed(&mut self, allowed: bool) {
+        self.headers
+            .insert(MmsHeader::XMmsReportAllowed, allowed.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// M-Read-Rec.ind, a read report for a message which asked for one with
+/// X-Mms-Read-Report
+pub struct MReadRecInd {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MReadRecInd {
+    /// `message_id` is the Message-ID of the message which was read, and `to`
+    /// is the address it was sent from
+    pub fn new(
+        message_id: String,
+        to: Address,
+        from: mms_header_types::FromField,
+        status: ReadStatusField,
+    ) -> Self {
+        let mut headers = pdu_headers(MessageTypeField::MReadRecInd, None);
+        headers.insert(MmsHeader::MessageID, message_id.into());
+        headers.insert(MmsHeader::To, to.into());
+        headers.insert(MmsHeader::From, from.into());
+        headers.insert(MmsHeader::XMmsReadStatus, status.into());
+        Self { headers }
+    }
+    /// When the message was read
+    pub fn date(&mut self, date: SystemTime) {
+        self.headers.insert(MmsHeader::Date, date.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// M-Forward.req, asks the MMSC to forward a message it's still storing,
+/// without downloading and sending it again
+pub struct MForwardReq {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MForwardReq {
+    /// `content_location` is the X-Mms-Content-Location from the message's
+    /// M-Notification.ind. The MMSC fills in the From address unless
+    /// [`from`](Self::from) is used
+    pub fn new(content_location: String) -> Self {
+        let mut headers = pdu_headers(
+            MessageTypeField::MForwardReq,
+            Some(uuid::Uuid::new_v4().to_string()),
+        );
+        headers.insert(
+            MmsHeader::From,
+            mms_header_types::FromField::InsertAddress.into(),
+        );
+        headers.insert(MmsHeader::XMmsContentLocation, content_location.into());
+        Self { headers }
+    }
+    pub fn transaction_id(&mut self, id: String) {
+        self.headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    pub fn from(&mut self, addr: mms_header_types::FromField) {
+        self.headers.insert(MmsHeader::From, addr.into());
+    }
+    /// Add a recipient, this can be called more than once to forward the
+    /// message to several recipients
+    pub fn to(&mut self, addr: Address) {
+        self.headers.append(MmsHeader::To, addr.into());
+    }
+    pub fn cc(&mut self, addr: Address) {
+        self.headers.append(MmsHeader::Cc, addr.into());
+    }
+    pub fn bcc(&mut self, addr: Address) {
+        self.headers.append(MmsHeader::Bcc, addr.into());
+    }
+    pub fn date(&mut self, date: SystemTime) {
+        self.headers.insert(MmsHeader::Date, date.into());
+    }
+    pub fn delivery_report(&mut self, report: bool) {
+        self.headers.insert(
+            MmsHeader::XMmsDeliveryReport,
+            YesNoField::from(report).into(),
+        );
+    }
+    pub fn read_report(&mut self, report: bool) {
+        self.headers
+            .insert(MmsHeader::XMmsReadReport, YesNoField::from(report).into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// M-Mbox-Store.req, asks the MMSC to keep a message which is waiting to be
+/// fetched in the MMBox, or to change the state and flags of one already there
+pub struct MMboxStoreReq {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MMboxStoreReq {
+    /// `content_location` is the X-Mms-Content-Location from the message's
+    /// M-Notification.ind, or from it's M-Mbox-Descr
+    pub fn new(content_location: String) -> Self {
+        let mut headers = pdu_headers(
+            MessageTypeField::MMboxStoreReq,
+            Some(uuid::Uuid::new_v4().to_string()),
+        );
+        headers.insert(MmsHeader::XMmsContentLocation, content_location.into());
+        Self { headers }
+    }
+    pub fn transaction_id(&mut self, id: String) {
+        self.headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    pub fn mm_state(&mut self, state: MMStateField) {
+        self.headers.insert(MmsHeader::XMmsMMState, state.into());
+    }
+    /// Add or remove a keyword, this can be called more than once
+    pub fn mm_flags(&mut self, flags: MMFlagsField) {
+        self.headers.append(MmsHeader::XMmsMMFlags, flags.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// M-Mbox-View.req, asks the MMSC which messages are in the MMBox, and how
+/// much of it is used
+pub struct MMboxViewReq {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MMboxViewReq {
+    /// Without any filters every message in the MMBox is listed
+    pub fn new() -> Self {
+        let headers = pdu_headers(
+            MessageTypeField::MMboxViewReq,
+            Some(uuid::Uuid::new_v4().to_string()),
+        );
+        Self { headers }
+    }
+    pub fn transaction_id(&mut self, id: String) {
+        self.headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    /// Only list this message, this can be called more than once
+    pub fn content_location(&mut self, location: String) {
+        self.headers
+            .append(MmsHeader::XMmsContentLocation, location.into());
+    }
+    /// Only list messages in this state, this can be called more than once
+    pub fn mm_state(&mut self, state: MMStateField) {
+        self.headers.append(MmsHeader::XMmsMMState, state.into());
+    }
+    /// Only list messages matching this flag, this can be called more than
+    /// once
+    pub fn mm_flags(&mut self, flags: MMFlagsField) {
+        self.headers.append(MmsHeader::XMmsMMFlags, flags.into());
+    }
+    /// The index of the first message to list, starting at 0
+    pub fn start(&mut self, start: u64) {
+        self.headers.insert(MmsHeader::XMmsStart, start.into());
+    }
+    /// The most messages to list
+    pub fn limit(&mut self, limit: u64) {
+        self.headers.insert(MmsHeader::XMmsLimit, limit.into());
+    }
+    /// Include this header in each M-Mbox-Descr, this can be called more than
+    /// once
+    pub fn attribute(&mut self, header: MmsHeader) {
+        self.headers
+            .append(MmsHeader::XMmsAttributes, header.into());
+    }
+    /// Whether to include the number and size of messages in the MMBox
+    pub fn totals(&mut self, totals: bool) {
+        self.headers.insert(MmsHeader::XMmsTotals, totals.into());
+    }
+    /// Whether to include the number and size of messages the MMBox can hold
+    pub fn quotas(&mut self, quotas: bool) {
+        self.headers.insert(MmsHeader::XMmsQuotas, quotas.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+impl Default for MMboxViewReq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// M-Mbox-Upload.req, stores a message in the MMBox without sending it
+pub struct MMboxUploadReq {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+    message: Vec<u8>,
+}
+
+impl MMboxUploadReq {
+    /// `message` is an encoded message, such as from [`MSendReq::encode`]
+    pub fn new(message: Vec<u8>) -> Self {
+        let headers = pdu_headers(
+            MessageTypeField::MMboxUploadReq,
+            Some(uuid::Uuid::new_v4().to_string()),
+        );
+        Self { headers, message }
+    }
+    pub fn transaction_id(&mut self, id: String) {
+        self.headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    pub fn mm_state(&mut self, state: MMStateField) {
+        self.headers.insert(MmsHeader::XMmsMMState, state.into());
+    }
+    /// Add a keyword, this can be called more than once
+    pub fn mm_flags(&mut self, flags: MMFlagsField) {
+        self.headers.append(MmsHeader::XMmsMMFlags, flags.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        let content_type: mime::Mime =
+            "application/vnd.wap.mms-message".parse().unwrap();
+        encode_mms_message(self.headers, (content_type, self.message))
+    }
+}
+
+/// M-Mbox-Delete.req, deletes messages from the MMBox
+pub struct MMboxDeleteReq {
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+}
+
+impl MMboxDeleteReq {
+    /// `content_location` is the X-Mms-Content-Location from the message's
+    /// M-Mbox-Descr
+    pub fn new(content_location: String) -> Self {
+        let mut headers = pdu_headers(
+            MessageTypeField::MMboxDeleteReq,
+            Some(uuid::Uuid::new_v4().to_string()),
+        );
+        headers.insert(MmsHeader::XMmsContentLocation, content_location.into());
+        Self { headers }
+    }
+    pub fn transaction_id(&mut self, id: String) {
+        self.headers.insert(MmsHeader::XMmsTransactionId, id.into());
+    }
+    /// Delete another message, the M-Mbox-Delete.conf refers to messages by
+    /// the order they were added in, starting at 0
+    pub fn content_location(&mut self, location: String) {
+        self.headers
+            .append(MmsHeader::XMmsContentLocation, location.into());
+    }
+    pub fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_mms_headers(self.headers)
+    }
+}
+
+/// Encode a message, the headers are written in the order they were
+/// inserted, except for the ones the spec requires to be in a specific place:
+///
+/// "In the encoding of the header fields, the order of the fields is not
+/// significant, except that X-Mms-Message-Type, X-Mms-Transaction-ID (when
+/// present) and X-Mms-MMS-Version MUST be at the beginning of the message
+/// headers, in that order, and if the PDU contains a message body the
+/// Content Type MUST be the last header field, followed by message body."
+///
+/// The Content-Type is taken from `body`, so `headers` must not contain one.
 pub fn encode_mms_message(
     headers: MultiMap<MmsHeader, MmsHeaderValue>,
     body: impl EncodableBody,
-) -> Vec<u8> {
-    let mut encoded = Vec::new();
+) -> Result<Vec<u8>, EncodeError> {
+    encode_message(headers, body, None)
+}
 
-    if let Some(_) = headers.get(&MmsHeader::ContentType) {
-        // TODO: Remove need to panic
-        panic!("Headers must not contain a content_type");
+/// [`encode_mms_message`], with the subject encoded in `charset`
+fn encode_message(
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+    body: impl EncodableBody,
+    charset: Option<Charset>,
+) -> Result<Vec<u8>, EncodeError> {
+    if headers.contains_key(&MmsHeader::ContentType) {
+        return Err(EncodeError::DuplicateHeader(MmsHeader::ContentType));
     }
 
-    // TODO: Enforce the following:
-    // "In the encoding of the header fields, the order of the fields is not
-    // significant, except that X-Mms-Message-Type, X-Mms-Transaction-ID (when
-    // present) and X-Mms-MMS-Version MUST be at the beginning of the message
-    // headers, in that order, and if the PDU contains a message body the
-    // Content Type MUST be the last header field, followed by message body."
-    let encoded_headers: Vec<Vec<u8>> = headers
-        .iter()
-        .map(|header| {
-            mms_header::encode_header_field(header.0.clone(), header.1.clone())
-                .unwrap()
+    let mut encoded = encode_headers(headers, charset)?;
+    encoded.append(&mut mms_header::encode_header_field(
+        MmsHeader::ContentType,
+        body.content_type().clone().into(),
+    )?);
+    encoded.append(&mut body.encode()?);
+
+    Ok(encoded)
+}
+
+/// Encode a message without a body, such as a M-NotifyResp.ind, the headers
+/// are ordered the same way as [`encode_mms_message`]
+pub fn encode_mms_headers(
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+) -> Result<Vec<u8>, EncodeError> {
+    encode_headers(headers, None)
+}
+
+fn encode_headers(
+    headers: MultiMap<MmsHeader, MmsHeaderValue>,
+    charset: Option<Charset>,
+) -> Result<Vec<u8>, EncodeError> {
+    use MmsHeader::*;
+    const FIRST: [MmsHeader; 3] =
+        [XMmsMessageType, XMmsTransactionId, XMmsMMSVersion];
+
+    for header in headers.keys() {
+        if !header.is_repeatable() && headers.get_all(header).count() > 1 {
+            return Err(EncodeError::DuplicateHeader(header.clone()));
+        }
+    }
+
+    let mut sorted: Vec<(&MmsHeader, &MmsHeaderValue)> =
+        headers.iter().collect();
+    // The sort is stable, so everything else stays in insertion order
+    sorted.sort_by_key(|(header, _)| {
+        FIRST
+            .iter()
+            .position(|h| h == *header)
+            .unwrap_or(FIRST.len())
+    });
+
+    let mut encoded = Vec::new();
+    for (header, value) in sorted {
+        encoded.append(&mut mms_header::encode_header_field_in_charset(
+            header.clone(),
+            value.clone(),
+            charset,
+        )?);
+    }
+
+    Ok(encoded)
+}
+
+/// Encode a message which was parsed with [`parse_mms_pdu`](crate::parse_mms_pdu),
+/// keeping the original bytes and position of every header which wasn't
+/// changed, so headers this crate doesn't understand aren't lost.
+///
+/// Headers which were changed are encoded where they first appeared, and new
+/// headers are added before the Content-Type.
+pub fn encode_mms_message_lossless(
+    message: &VndWapMmsMessage<'_>,
+) -> Result<Vec<u8>, EncodeError> {
+    let raw_headers = &message.raw_headers;
+    let message_type = crate::pdu::find_message_type(raw_headers);
+    let mut encoded = Vec::new();
+
+    // A header is unchanged if every value for it is the same as what is
+    // parsed from it's raw values
+    let mut unchanged: HashMap<MmsHeader, bool> = HashMap::new();
+    let mut is_unchanged = |header: &MmsHeader| {
+        *unchanged.entry(header.clone()).or_insert_with(|| {
+            let original: Vec<MmsHeaderValue> = raw_headers
+                .iter()
+                .filter(|(h, _)| h == header)
+                .filter_map(|(h, v)| {
+                    crate::pdu::parse_header_value(message_type.as_ref(), h, v)
+                        .ok()
+                })
+                .collect();
+            message.headers.get_all(header).eq(original.iter())
         })
+    };
+
+    let new_headers: Vec<(&MmsHeader, &MmsHeaderValue)> = message
+        .headers
+        .iter()
+        .filter(|(h, _)| !raw_headers.iter().any(|(raw, _)| raw == *h))
         .collect();
+    let encode_new = |encoded: &mut Vec<u8>| -> Result<(), EncodeError> {
+        let mut new_headers = new_headers.clone();
+        // The Content-Type has to be last
+        new_headers.sort_by_key(|(h, _)| **h == MmsHeader::ContentType);
+        for (header, value) in new_headers {
+            encoded.append(&mut mms_header::encode_header_field(
+                header.clone(),
+                value.clone(),
+            )?);
+        }
+        Ok(())
+    };
 
-    encoded.append(&mut encoded_headers.concat());
-    encoded.append(
-        &mut mms_header::encode_header_field(
-            MmsHeader::ContentType,
-            body.content_type().clone().into(),
-        )
-        .unwrap(),
-    );
-    encoded.append(&mut body.encode());
+    let mut written = Vec::new();
+    for (header, value) in raw_headers {
+        if *header == MmsHeader::ContentType {
+            encode_new(&mut encoded)?;
+        }
 
-    encoded
+        if is_unchanged(header) {
+            let mut header_bytes: Vec<u8> = header.clone().into();
+            encoded.append(&mut header_bytes);
+            encoded.extend_from_slice(value);
+        } else if !written.contains(header) {
+            for value in message.headers.get_all(header) {
+                encoded.append(&mut mms_header::encode_header_field(
+                    header.clone(),
+                    value.clone(),
+                )?);
+            }
+            written.push(header.clone());
+        }
+    }
+
+    if !raw_headers
+        .iter()
+        .any(|(h, _)| *h == MmsHeader::ContentType)
+    {
+        encode_new(&mut encoded)?;
+    }
+
+    encoded.extend_from_slice(&message.body);
+    Ok(encoded)
+}
+
+fn encode_accept_charset(
+    v: crate::types::message_header::AcceptCharsetValue,
+) -> Vec<u8> {
+    use crate::types::message_header::CharsetValue;
+
+    let mut buf = match v.charset {
+        CharsetValue::Any => vec![128],
+        CharsetValue::Code(c) => encode_integer_value(c),
+        CharsetValue::Name(n) => encode_string(n),
+    };
+
+    // A Long-integer code, or a Q-value, needs the general form
+    match v.quality {
+        Some(q) if q < 1000 => {
+            let q = u64::from(q);
+            let mut q = match q % 10 {
+                0 => encode_uintvar(q / 10 + 1),
+                _ => encode_uintvar(q + 100),
+            };
+            buf.append(&mut q);
+            value_length(buf)
+        }
+        _ if matches!(buf.first(), Some(0..=31)) => value_length(buf),
+        _ => buf,
+    }
 }
 
-fn encode_wap_headers(headers: Vec<MessageHeader>) -> Vec<u8> {
+fn encode_wap_headers(
+    headers: Vec<MessageHeader>,
+) -> Result<Vec<u8>, EncodeError> {
+    use crate::types::message_header::{LanguageValue, MessageHeader::*};
     let mut buf = Vec::new();
-    use crate::types::message_header::MessageHeader::*;
 
+    // From wap-230-wsp table 39
     for header in headers {
-        match header {
-            ContentLocation(v) => {
-                buf.push(0x0E | 0x80);
-                buf.append(&mut encode_string(v));
+        let (code, mut value) = match header {
+            AcceptCharset(v) => (0x01, encode_accept_charset(v)),
+            ContentBase(v) => (0x0A, encode_string(v)),
+            ContentEncoding(v) => (
+                0x0B,
+                match &*v {
+                    "gzip" => vec![128],
+                    "compress" => vec![129],
+                    "deflate" => vec![130],
+                    _ => encode_string(v),
+                },
+            ),
+            ContentLanguage(v) => (
+                0x0C,
+                match v {
+                    LanguageValue::Any => vec![128],
+                    LanguageValue::Code(c) => encode_integer_value(c),
+                    LanguageValue::Name(n) => encode_string(n),
+                },
+            ),
+            ContentLength(v) => (0x0D, encode_integer_value(v as u64)),
+            ContentLocation(v) => (0x0E, encode_string(v)),
+            ContentMd5(v) => (0x0F, value_length(v)),
+            Date(v) => (0x12, encode_date_value(v)),
+            Etag(v) => (0x13, encode_string(v)),
+            Expires(v) => (0x14, encode_date_value(v)),
+            LastModified(v) => (0x1D, encode_date_value(v)),
+            ContentDisposition(v) => {
+                let mut value = match &*v.disposition {
+                    "form-data" => vec![128],
+                    "attachment" => vec![129],
+                    "inline" => vec![130],
+                    _ => encode_string(v.disposition),
+                };
+                for (name, param) in v.parameters {
+                    value.append(&mut encode_parameter(&name, &param));
+                }
+                (0x2E, value_length(value))
             }
-            ContentId(v) => {
-                buf.push(0x40 | 0x80);
-                buf.append(&mut encode_quoted_string(v));
+            XWapApplicationId(v) => (0x2F, encode_integer_value(v as u64)),
+            XWapContentUri(v) => (0x30, encode_string(v)),
+            XWapInitiatorUri(v) => (0x31, encode_string(v)),
+            PushFlag(v) => (0x34, vec![v | 0x80]),
+            ContentId(v) => (0x40, encode_quoted_string(v)?),
+            EncodingVersion(v) => (0x43, encode_version_value(&v)),
+            ApplicationHeader((name, v)) => {
+                buf.append(&mut encode_string(name));
+                if v.is_empty() {
+                    buf.push(0);
+                } else {
+                    buf.append(&mut encode_string(v));
+                }
+                continue;
             }
-            b => panic!("Unable to encode {:?}, no known encoder", b),
-        }
+            ShiftCodePage(page) => {
+                if (1..=31).contains(&page) {
+                    buf.push(page);
+                } else {
+                    buf.append(&mut vec![0x7F, page]);
+                }
+                continue;
+            }
+            UnknownHeader((code, v)) => (code, v),
+        };
+
+        buf.push(code | 0x80);
+        buf.append(&mut value);
     }
 
-    buf
+    Ok(buf)
 }
 
 pub(crate) fn encode_uintvar(num: u64) -> Vec<u8> {
@@ -272,17 +875,17 @@ fn encode_byte(b: u8) -> Vec<u8> {
     vec![b]
 }
 
-fn encode_quoted_string(v: String) -> Vec<u8> {
+fn encode_quoted_string(v: String) -> Result<Vec<u8>, EncodeError> {
     let mut buf = encode_string(v);
 
     if let Some(n) = buf.get(1) {
         if n > &0x7F {
-            panic!("Invalid character in quoted string");
+            return Err("Invalid character in quoted string".into());
         }
     }
 
     buf.insert(0, '"' as u8);
-    buf
+    Ok(buf)
 }
 
 // TODO: There are multiple string types with different allowed characters, add
@@ -296,6 +899,28 @@ fn encode_string(v: String) -> Vec<u8> {
     bytes
 }
 
+/// Encode text as an Encoded-string-value, without a charset ascii text is
+/// written as a plain Text-string, and anything else as UTF-8
+fn encode_encoded_string(v: String, charset: Option<Charset>) -> Vec<u8> {
+    let (charset, mut text) = match charset {
+        None if v.is_ascii() => return encode_string(v),
+        None => (Charset::UTF_8, v.into_bytes()),
+        Some(c) => match c.encode(&v) {
+            Ok(text) => (c, text),
+            Err(_) => (Charset::UTF_8, v.into_bytes()),
+        },
+    };
+
+    let mut buf = encode_integer_value(charset.mib_enum());
+    if !charset.is_utf_16() && matches!(text.first(), Some(128..=255)) {
+        buf.push(127);
+    }
+    buf.append(&mut text);
+    buf.push(0);
+
+    value_length(buf)
+}
+
 fn encode_bool(v: bool) -> u8 {
     match v {
         true => 128,
@@ -311,6 +936,32 @@ fn encode_short_integer(v: u8) -> Result<Vec<u8>, &'static str> {
     Ok(vec![v | 0x80])
 }
 
+fn encode_long_integer(v: u64) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(7);
+
+    let mut buf = vec![(8 - start) as u8];
+    buf.extend_from_slice(&bytes[start..]);
+    buf
+}
+
+fn encode_integer_value(v: u64) -> Vec<u8> {
+    if v <= 0x7F {
+        vec![v as u8 | 0x80]
+    } else {
+        encode_long_integer(v)
+    }
+}
+
+fn encode_date_value(v: SystemTime) -> Vec<u8> {
+    // Dates before 1970 can't be represented
+    let seconds = v
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    encode_long_integer(seconds)
+}
+
 fn encode_value_length(len: u64) -> Vec<u8> {
     if len <= 30 {
         vec![u8::try_from(len).unwrap()]
@@ -328,8 +979,8 @@ fn value_length(mut v: Vec<u8>) -> Vec<u8> {
     len_bytes
 }
 
-fn encode_address(v: String) -> Vec<u8> {
-    let mut inner = encode_string(v);
+fn encode_address(v: Address) -> Vec<u8> {
+    let mut inner = encode_encoded_string(v.to_string(), None);
     let mut outer = vec![128];
     outer.append(&mut inner);
     value_length(outer)
@@ -351,6 +1002,96 @@ mod test {
         )
     }
 
+    #[test]
+    fn wap_headers_round_trip() {
+        use crate::types::message_header::{
+            AcceptCharsetValue, CharsetValue, ContentDisposition,
+            LanguageValue, MessageHeader::*,
+        };
+
+        let encoded = encode_wap_headers(vec![
+            ContentDisposition(ContentDisposition {
+                disposition: "attachment".to_string(),
+                parameters: vec![("filename".to_string(), "a.jpg".to_string())],
+            }),
+            ContentLanguage(LanguageValue::Name("en".to_string())),
+            ContentLength(1000),
+            AcceptCharset(AcceptCharsetValue {
+                charset: CharsetValue::Code(1015),
+                quality: Some(1),
+            }),
+            AcceptCharset(AcceptCharsetValue {
+                charset: CharsetValue::Name("koi8-r".to_string()),
+                quality: Some(500),
+            }),
+            AcceptCharset(AcceptCharsetValue {
+                charset: CharsetValue::Any,
+                quality: None,
+            }),
+            ApplicationHeader(("X-Custom".to_string(), "v".to_string())),
+            ShiftCodePage(2),
+            UnknownHeader((0x05, vec![0x81])),
+        ])
+        .unwrap();
+        let (_, decoded) = crate::parser::wap_headers(&encoded).unwrap();
+
+        assert_eq!(encode_wap_headers(decoded).unwrap(), encoded);
+    }
+
+    #[test]
+    fn encode_long_integer_without_leading_zeros() {
+        assert_eq!(vec![2, 0x03, 0xF7], encode_long_integer(1015));
+        assert_eq!(vec![1, 0], encode_long_integer(0));
+    }
+
+    #[test]
+    fn ascii_subject_is_text_string() {
+        assert_eq!(encode_encoded_string("hi".to_string(), None), b"hi\0");
+    }
+
+    #[test]
+    fn non_ascii_subject_is_utf_8() {
+        assert_eq!(
+            encode_encoded_string("\u{e9}".to_string(), None),
+            b"\x05\xEA\x7F\xC3\xA9\0"
+        );
+    }
+
+    #[test]
+    fn utf_16_subject_round_trip() {
+        let encoded = encode_encoded_string(
+            "h\u{410}".to_string(),
+            Some(Charset::UTF_16),
+        );
+        let (_, decoded) =
+            crate::parser::parse_encoded_string_value(&encoded).unwrap();
+
+        assert_eq!(decoded, "h\u{410}");
+    }
+
+    #[test]
+    fn subject_is_encoded_in_charset() {
+        let mut message = MSendReq::new();
+        message.subject("h\u{410}".to_string());
+        message.charset(Charset::UTF_16);
+        message.body_text("text");
+
+        assert_eq!(
+            message.get(&MmsHeader::Subject),
+            Some(&MmsHeaderValue::String("h\u{410}".to_string()))
+        );
+
+        let encoded = message.encode().unwrap();
+        let subject = [
+            0x96, 0x0A, 0x02, 0x03, 0xF7, 0xFE, 0xFF, 0x00, b'h', 0x04, 0x10,
+            0x00,
+        ];
+        assert!(encoded.windows(subject.len()).any(|w| w == subject));
+
+        let parsed = crate::parse_mms_pdu(&encoded).unwrap();
+        assert_eq!(parsed.subject(), Some(&"h\u{410}".to_string()));
+    }
+
     #[test]
     fn encode_multi_byte_uintvar() {
         assert_eq!(
@@ -358,4 +1099,427 @@ mod test {
             encode_uintvar(0b1000000000000000000011u64)
         );
     }
+
+    const LOSSLESS_PDU: &[u8] = &[
+        0x8C, 0x80, 0x98, b't', 0, 0x8D, 0x92, // Type, id, and version
+        0x89, 0x01, 0x85, // From with an invalid value
+        0xFF, b'x', 0, // Unknown header
+        0x96, b'h', b'i', 0, // Subject
+        0x84, 0x83, b'b', b'o', b'd', b'y', // Content-Type and body
+    ];
+
+    #[test]
+    fn lossless_round_trip() {
+        let message = crate::parse_mms_pdu(LOSSLESS_PDU).unwrap();
+        assert!(message.from().is_none());
+
+        assert_eq!(
+            encode_mms_message_lossless(&message).unwrap(),
+            LOSSLESS_PDU
+        );
+    }
+
+    #[test]
+    fn lossless_with_changed_headers() {
+        let mut message = crate::parse_mms_pdu(LOSSLESS_PDU).unwrap();
+        message
+            .headers
+            .insert(MmsHeader::XMmsTransactionId, "u".to_string().into());
+        message.headers.remove(&MmsHeader::Subject);
+        message
+            .headers
+            .insert(MmsHeader::XMmsDeliveryReport, YesNoField::Yes.into());
+
+        assert_eq!(
+            encode_mms_message_lossless(&message).unwrap(),
+            [
+                0x8C, 0x80, 0x98, b'u', 0, 0x8D, 0x92, 0x89, 0x01, 0x85, 0xFF,
+                b'x', 0, 0x86, 0x80, 0x84, 0x83, b'b', b'o', b'd', b'y',
+            ]
+        );
+    }
+
+    #[test]
+    fn several_recipients() {
+        let address = |n: &str| Address::Plmn(n.to_string());
+
+        let mut message = MSendReq::new();
+        message.to(address("+1"));
+        message.to(address("+2"));
+        message.cc(address("+3"));
+        message.bcc(address("+4"));
+        message.bcc(address("+5"));
+        message.body_text("hi");
+
+        let encoded = message.encode().unwrap();
+        let parsed = crate::parse_mms_pdu(&encoded).unwrap();
+
+        assert_eq!(parsed.to(), [address("+1"), address("+2")]);
+        assert_eq!(parsed.cc(), [address("+3")]);
+        assert_eq!(parsed.bcc(), [address("+4"), address("+5")]);
+    }
+
+    #[test]
+    fn every_header_round_trips() {
+        use crate::types::mms_header::*;
+        use std::time::Duration;
+        use MmsHeader::*;
+
+        let date = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let address = Address::Plmn("+15551234".to_string());
+        let headers: Vec<(MmsHeader, MmsHeaderValue)> = vec![
+            (Bcc, address.clone().into()),
+            (Date, date.into()),
+            (From, FromField::InsertAddress.into()),
+            (MessageID, "id".to_string().into()),
+            (Subject, "\u{410}".to_string().into()),
+            (XMmsAttributes, MmsHeader::Subject.into()),
+            (XMmsContentLocation, "http://a/b".to_string().into()),
+            (
+                XMmsDeliveryTime,
+                ExpiryField::Relative(Duration::from_secs(60)).into(),
+            ),
+            (XMmsExpiry, ExpiryField::Absolute(date).into()),
+            (
+                XMmsElementDescriptor,
+                ElementDescriptor {
+                    content_reference: "part".to_string(),
+                    parameters: vec![(
+                        "type".to_string(),
+                        "text/plain".to_string(),
+                    )],
+                }
+                .into(),
+            ),
+            (XMmsLimit, 1000u64.into()),
+            (XMmsMMFlags, MMFlagsField::Remove("seen".to_string()).into()),
+            (XMmsMboxQuotas, MboxQuantityField::Size(100_000).into()),
+            (XMmsMessageSize, 100u64.into()),
+            (
+                XMmsPreviouslySentBy,
+                PreviouslySentBy {
+                    forward_count: 2,
+                    address,
+                }
+                .into(),
+            ),
+            (
+                XMmsPreviouslySentDate,
+                PreviouslySentDate {
+                    forward_count: 2,
+                    date,
+                }
+                .into(),
+            ),
+            (XMmsPriority, PriorityField::High.into()),
+            (
+                XMmsRetrieveStatus,
+                RetrieveStatusField::ErrorPermanentMessageNotFound.into(),
+            ),
+            (
+                XMmsResponseStatus,
+                ResponseStatusField::ErrorPermanentLackOfPrepaid.into(),
+            ),
+            (XMmsSenderVisibility, SenderVisibilityField::Hide.into()),
+            (XMmsContentClass, ContentClassField::Unknown(140).into()),
+            (XMmsStatus, StatusField::Deferred.into()),
+            (XMmsStored, true.into()),
+            (
+                XMmsResponseStatus,
+                IndexedResponseStatus {
+                    index: 1,
+                    value: ResponseStatusField::ErrorPermanentMessageNotFound,
+                }
+                .into(),
+            ),
+            (
+                XMmsResponseText,
+                IndexedString {
+                    index: 2,
+                    value: "\u{410}".to_string(),
+                }
+                .into(),
+            ),
+            (
+                XMmsContentLocation,
+                IndexedString {
+                    index: 300,
+                    value: "http://a/b".to_string(),
+                }
+                .into(),
+            ),
+        ];
+
+        for (header, value) in headers {
+            let encoded =
+                mms_header::encode_header_field(header.clone(), value.clone())
+                    .unwrap();
+            // The indexed values are only parsed in a M-Delete.conf
+            let parse = match value {
+                MmsHeaderValue::IndexedResponseStatus(_)
+                | MmsHeaderValue::IndexedString(_) => {
+                    crate::parser::mms_header::parse_delete_conf_field
+                }
+                _ => crate::parser::mms_header::parse_header_field,
+            };
+            let (rest, parsed) = parse(header.clone(), &encoded[1..]).unwrap();
+
+            assert!(rest.is_empty(), "{:?} wasn't fully parsed", header);
+            assert_eq!(parsed, value, "{:?} didn't round trip", header);
+        }
+    }
+
+    #[test]
+    fn headers_in_canonical_order() {
+        let mut headers = MultiMap::new();
+        headers.insert(MmsHeader::Subject, "hi".to_string().into());
+        headers.insert(MmsHeader::XMmsMMSVersion, crate::MMS_VERSION.into());
+        headers.insert(MmsHeader::XMmsTransactionId, "t".to_string().into());
+        headers.insert(
+            MmsHeader::XMmsMessageType,
+            MessageTypeField::MSendReq.into(),
+        );
+
+        let body = (mime::TEXT_PLAIN, b"body".to_vec());
+        assert_eq!(
+            encode_mms_message(headers, body).unwrap(),
+            [
+                0x8C, 0x80, 0x98, b't', 0, 0x8D, 0x92, 0x96, b'h', b'i', 0,
+                0x84, 0x83, b'b', b'o', b'd', b'y',
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_headers_are_rejected() {
+        let body = || (mime::TEXT_PLAIN, b"body".to_vec());
+
+        let mut headers = MultiMap::new();
+        headers.append(MmsHeader::Subject, "a".to_string().into());
+        headers.append(MmsHeader::Subject, "b".to_string().into());
+        assert!(matches!(
+            encode_mms_message(headers, body()),
+            Err(EncodeError::DuplicateHeader(MmsHeader::Subject))
+        ));
+
+        let mut headers = MultiMap::new();
+        headers.insert(MmsHeader::ContentType, mime::TEXT_PLAIN.into());
+        assert!(matches!(
+            encode_mms_message(headers, body()),
+            Err(EncodeError::DuplicateHeader(MmsHeader::ContentType))
+        ));
+    }
+
+    #[test]
+    fn bad_files_are_errors() {
+        let mut message = MSendReq::new();
+
+        assert!(matches!(
+            message.body_file("does-not-exist.txt"),
+            Err(EncodeError::Io(_))
+        ));
+        assert!(matches!(
+            message.body_file("Cargo.unknown-extension"),
+            Err(EncodeError::UnknownContentType(_))
+        ));
+        assert!(matches!(
+            message.body_file(".."),
+            Err(EncodeError::NoFileName(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_content_id_is_an_error() {
+        use crate::types::message_header::MessageHeader::ContentId;
+
+        assert!(
+            encode_wap_headers(vec![ContentId("\u{e9}".to_string())]).is_err()
+        );
+    }
+
+    #[test]
+    fn notify_resp_round_trip() {
+        let mut notify_resp =
+            MNotifyRespInd::new("t".to_string(), StatusField::Retrieved);
+        notify_resp.report_allowed(false);
+        let encoded = notify_resp.encode().unwrap();
+
+        assert_eq!(
+            encoded,
+            [0x8C, 0x83, 0x98, b't', 0, 0x8D, 0x92, 0x95, 0x81, 0x91, 0x81]
+        );
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MNotifyrespInd(n)) => {
+                assert_eq!(n.x_mms_status, StatusField::Retrieved);
+                assert_eq!(n.x_mms_report_allowed, Some(false));
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn acknowledge_round_trip() {
+        let encoded = MAcknowledgeInd::new("t".to_string()).encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MAcknowledgeInd(a)) => {
+                assert_eq!(a.x_mms_transaction_id, "t");
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn read_rec_round_trip() {
+        use crate::types::mms_header::FromField;
+        use std::time::Duration;
+
+        let date = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut read_rec = MReadRecInd::new(
+            "id".to_string(),
+            Address::Plmn("+1".to_string()),
+            FromField::InsertAddress,
+            ReadStatusField::Read,
+        );
+        read_rec.date(date);
+        let encoded = read_rec.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MReadRecInd(r)) => {
+                assert_eq!(r.message_id, "id");
+                assert_eq!(r.to, [Address::Plmn("+1".to_string())]);
+                assert_eq!(r.from, FromField::InsertAddress);
+                assert_eq!(r.x_mms_read_status, ReadStatusField::Read);
+                assert_eq!(r.date, Some(date));
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn forward_req_round_trip() {
+        let mut forward = MForwardReq::new("http://mmsc/abc".to_string());
+        forward.transaction_id("t".to_string());
+        forward.to(Address::Plmn("+1".to_string()));
+        forward.to(Address::Plmn("+2".to_string()));
+        forward.read_report(true);
+        let encoded = forward.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MForwardReq(f)) => {
+                assert_eq!(f.x_mms_transaction_id, "t");
+                assert_eq!(f.x_mms_content_location, "http://mmsc/abc");
+                assert_eq!(f.to.len(), 2);
+                assert_eq!(f.x_mms_read_report, Some(YesNoField::Yes));
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn mbox_view_req_round_trip() {
+        let mut view = MMboxViewReq::new();
+        view.mm_state(MMStateField::Draft);
+        view.mm_state(MMStateField::Sent);
+        view.mm_flags(MMFlagsField::Filter("work".to_string()));
+        view.start(10);
+        view.limit(5);
+        view.attribute(MmsHeader::Subject);
+        view.attribute(MmsHeader::From);
+        view.totals(true);
+        let encoded = view.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MMboxViewReq(v)) => {
+                assert_eq!(
+                    v.x_mms_mm_state,
+                    [MMStateField::Draft, MMStateField::Sent]
+                );
+                assert_eq!(
+                    v.x_mms_mm_flags,
+                    [MMFlagsField::Filter("work".to_string())]
+                );
+                assert_eq!(v.x_mms_start, Some(10));
+                assert_eq!(v.x_mms_limit, Some(5));
+                assert_eq!(
+                    v.x_mms_attributes,
+                    [MmsHeader::Subject, MmsHeader::From]
+                );
+                assert_eq!(v.x_mms_totals, Some(true));
+                assert_eq!(v.x_mms_quotas, None);
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn mbox_store_and_delete_req_round_trip() {
+        let mut store = MMboxStoreReq::new("http://mmsc/a".to_string());
+        store.mm_state(MMStateField::Retrieved);
+        store.mm_flags(MMFlagsField::Add("seen".to_string()));
+        let encoded = store.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MMboxStoreReq(s)) => {
+                assert_eq!(s.x_mms_content_location, "http://mmsc/a");
+                assert_eq!(s.x_mms_mm_state, Some(MMStateField::Retrieved));
+                assert_eq!(
+                    s.x_mms_mm_flags,
+                    [MMFlagsField::Add("seen".to_string())]
+                );
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+
+        let mut delete = MMboxDeleteReq::new("http://mmsc/a".to_string());
+        delete.content_location("http://mmsc/b".to_string());
+        let encoded = delete.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MMboxDeleteReq(d)) => {
+                assert_eq!(
+                    d.x_mms_content_location,
+                    ["http://mmsc/a", "http://mmsc/b"]
+                );
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
+
+    #[test]
+    fn mbox_upload_req_contains_message() {
+        let mut message = MSendReq::new();
+        message.to(Address::Plmn("+1".to_string()));
+        message.body_text("hi");
+        let message = message.encode().unwrap();
+
+        let mut upload = MMboxUploadReq::new(message.clone());
+        upload.mm_state(MMStateField::Draft);
+        let encoded = upload.encode().unwrap();
+
+        match crate::types::Pdu::try_from(
+            crate::parse_mms_pdu(&encoded).unwrap(),
+        ) {
+            Ok(crate::types::Pdu::MMboxUploadReq(u)) => {
+                assert_eq!(u.content_type, "application/vnd.wap.mms-message");
+                assert_eq!(u.x_mms_mm_state, Some(MMStateField::Draft));
+                assert_eq!(u.body, message);
+            }
+            p => panic!("Unexpected pdu {:?}", p),
+        }
+    }
 }
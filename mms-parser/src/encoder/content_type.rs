@@ -1,5 +1,8 @@
 use super::*;
-use crate::types::content_type_codes::CONTENT_TYPE_CODES;
+use crate::types::{
+    content_type_codes::CONTENT_TYPE_CODES,
+    parameter_codes::{ParameterValue, PARAMETER_CODES},
+};
 
 enum ContentEssence {
     Short(u8),
@@ -14,60 +17,123 @@ fn short_content_type(essence_str: &str) -> ContentEssence {
         .unwrap_or(ContentEssence::Long(essence_str.to_owned()))
 }
 
-fn param(name: mime::Name, value: mime::Name) -> Vec<u8> {
-    use mime::*;
-    // From wap-230-wsp table 38
-    match &*name.as_str().to_lowercase() {
-        "charset" => {
-            let mut buf = vec![0x01];
-
-            if value == STAR {
-                buf.push(128);
-            } else {
-                // Encoders seem to need to be able to return errors
-                well_known_charset(value.as_str()).unwrap();
-            }
-
-            buf
+fn encode_q_value(q: &str) -> Option<Vec<u8>> {
+    let q: f64 = q.parse().ok()?;
+    if !(0.0..1.0).contains(&q) {
+        return None;
+    }
+
+    // Values with two digits are encoded as 1 to 100, and values with three
+    // digits as 101 to 1099
+    let hundredths = q * 100.0;
+    let q = if (hundredths.round() - hundredths).abs() < 1e-9 {
+        hundredths.round() as u64 + 1
+    } else {
+        (q * 1000.0).round() as u64 + 100
+    };
+
+    Some(encode_uintvar(q))
+}
+
+pub(super) fn encode_version_value(version: &str) -> Vec<u8> {
+    let mut parts = version.splitn(2, '.');
+    let major = parts.next().and_then(|m| m.parse::<u8>().ok());
+    let minor = match parts.next() {
+        Some(m) => m.parse::<u8>().ok(),
+        None => Some(0x0F),
+    };
+
+    match (major, minor) {
+        (Some(major), Some(minor)) if major < 8 && minor <= 0x0F => {
+            vec![0x80 | major << 4 | minor]
         }
-        "name" => {
-            let mut buf = encode_short_integer(0x05).unwrap();
-            buf.append(&mut super::encode_string(value.as_str().to_owned()));
-            buf
+        _ => encode_string(version.to_owned()),
+    }
+}
+
+fn is_token(v: &str) -> bool {
+    v.bytes()
+        .all(|c| c.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&c))
+}
+
+/// Text-value, which is either No-value, Token-text, or Quoted-string
+fn encode_text_value(v: &str) -> Vec<u8> {
+    if v.is_empty() {
+        vec![0]
+    } else if is_token(v) {
+        encode_string(v.to_owned())
+    } else {
+        let mut buf = vec![b'"'];
+        buf.extend_from_slice(v.as_bytes());
+        buf.push(0);
+        buf
+    }
+}
+
+fn encode_parameter_value(kind: ParameterValue, v: &str) -> Option<Vec<u8>> {
+    use ParameterValue::*;
+
+    Some(match kind {
+        QValue => encode_q_value(v)?,
+        WellKnownCharset if v == "*" => vec![128],
+        WellKnownCharset => {
+            encode_integer_value(crate::charset::charset_code(v)?)
         }
-        "type" => {
-            let mut buf = encode_short_integer(0x09).unwrap();
-            buf.append(&mut constrained_encoding(value.as_str()));
-            buf
+        VersionValue => encode_version_value(v),
+        IntegerValue | DeltaSecondsValue => {
+            encode_integer_value(v.parse().ok()?)
         }
-        "start" => {
-            let mut buf = encode_short_integer(0x0A).unwrap();
-            buf.append(&mut encode_string(value.as_str().to_owned()));
+        TextString => encode_string(v.to_owned()),
+        FieldName => match v.parse() {
+            Ok(code) => encode_short_integer(code).ok()?,
+            Err(_) => encode_string(v.to_owned()),
+        },
+        ShortInteger => encode_short_integer(v.parse().ok()?).ok()?,
+        ConstrainedEncoding => constrained_encoding(v),
+        NoValue if v.is_empty() => vec![0],
+        NoValue => return None,
+        TextValue => encode_text_value(v),
+        DateValue => encode_long_integer(v.parse().ok()?),
+    })
+}
 
-            buf
-        }
-        _ => untyped_param(name, value),
-    }
+/// Encode a parameter from wap-230-wsp table 38, or an untyped parameter
+pub(super) fn encode_parameter(name: &str, value: &str) -> Vec<u8> {
+    let lower_name = name.to_lowercase();
+
+    // Some names have multiple codes, and some values can't be encoded the
+    // way the parameter expects, so the first code which works is used, and
+    // anything else is sent as an untyped parameter
+    PARAMETER_CODES
+        .iter()
+        .filter(|(_, n, _)| *n == lower_name)
+        .find_map(|(code, _, kind)| {
+            let mut value = encode_parameter_value(*kind, value)?;
+            let mut buf = encode_short_integer(*code).ok()?;
+            buf.append(&mut value);
+            Some(buf)
+        })
+        .unwrap_or_else(|| untyped_param(name, value))
 }
 
 fn params(params: mime::Params) -> Vec<u8> {
-    let enc: Vec<Vec<u8>> =
-        params.map(|(name, value)| param(name, value)).collect();
+    let enc: Vec<Vec<u8>> = params
+        .map(|(name, value)| encode_parameter(name.as_str(), value.as_str()))
+        .collect();
     enc.concat()
 }
 
-fn well_known_charset(chr_set: &str) -> Option<u8> {
-    // From http://www.iana.org/assignments/character-sets/character-sets.xhtml
-    Some(match chr_set {
-        "US-ASCII" => 3,
-        "ISO-8859-1" => 4,
-        "UTF-8" => 106,
-        _ => return None,
-    })
-}
+/// Untyped-parameter, a Token-text name followed by either an Integer-value
+/// or a Text-value
+fn untyped_param(name: &str, value: &str) -> Vec<u8> {
+    let mut buf = encode_string(name.to_owned());
 
-fn untyped_param(_name: mime::Name, _value: mime::Name) -> Vec<u8> {
-    unimplemented!()
+    buf.append(&mut match value.parse() {
+        Ok(v) => encode_integer_value(v),
+        Err(_) => encode_text_value(value),
+    });
+
+    buf
 }
 
 fn general_form(essence: &str, mut params: Vec<u8>) -> Vec<u8> {
@@ -135,8 +201,14 @@ mod test {
     }
 
     #[test]
-    #[ignore]
-    // TODO: make this test pass
+    fn charset_param() {
+        let encoded =
+            encode_content_type("text/plain; charset=utf-8".parse().unwrap());
+
+        assert_eq!(encoded, b"\x03\x83\x81\xEA");
+    }
+
+    #[test]
     fn well_known_charset_unknown_param() {
         let encoded = encode_content_type(
             "application/vnd.wap.multipart.related; unknown=\"unknown\""
@@ -144,6 +216,20 @@ mod test {
                 .unwrap(),
         );
 
-        assert_eq!(encoded, b"\xB3unknown\0unknown\0\x09\x83");
+        assert_eq!(encoded, b"\x11\xB3unknown\0unknown\0");
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let content_type: mime::Mime = "image/jpeg; filename=\"a b.jpg\"; \
+            q=0.5; creation-date=256; level=1.2; x-id=\"c d\""
+            .parse()
+            .unwrap();
+
+        let encoded = encode_content_type(content_type.clone());
+        let (r, decoded) = crate::parser::parse_content_type(&encoded).unwrap();
+
+        assert_eq!(r, &[]);
+        assert_eq!(decoded, content_type);
     }
 }
@@ -1,45 +1,21 @@
 use super::*;
 use crate::types::{
-    mms_header, mms_header::ClassIdentifier, MmsHeader, MmsHeaderValue,
+    mms_header::{
+        self, ClassIdentifier, ElementDescriptor, ExpiryField, MMFlagsField,
+        MboxQuantityField, PreviouslySentBy, PreviouslySentDate,
+    },
+    Address, MmsHeader, MmsHeaderValue,
 };
 
-use std::{error::Error, fmt};
-
-#[derive(Debug)]
-pub(crate) enum EncodeError {
-    HeaderMsg((&'static str, MmsHeader)),
-    Msg(&'static str),
-}
-
-impl fmt::Display for EncodeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::HeaderMsg((msg, field)) => {
-                write!(f, "{} on field {:?}", msg, field)
-            }
-            Self::Msg(msg) => write!(f, "{}", msg),
-        }
-    }
-}
-
-impl Error for EncodeError {}
-
-impl std::convert::From<(&'static str, MmsHeader)> for EncodeError {
-    fn from(e: (&'static str, MmsHeader)) -> Self {
-        Self::HeaderMsg(e)
-    }
-}
-
-impl std::convert::From<&'static str> for EncodeError {
-    fn from(e: &'static str) -> Self {
-        Self::Msg(e)
-    }
-}
-
 macro_rules! encode_header_field_builder {
     ($($field_name:ident as $type:ident => $encode:expr),+$(,)*) => {
         #[allow(unused)]
         pub(crate) fn encode_header_field(field: MmsHeader, value: MmsHeaderValue) -> Result<Vec<u8>, EncodeError> {
+            if let Some(mut value_bytes) = encode_indexed(&field, &value) {
+                let mut header_bytes: Vec<u8> = field.into();
+                header_bytes.append(&mut value_bytes);
+                return Ok(header_bytes);
+            }
             let bytes = match field.clone() {
                 $(
                     MmsHeader::$field_name => {
@@ -59,8 +35,14 @@ macro_rules! encode_header_field_builder {
                         Ok(header_bytes)
                     }
                 )*
-                    field => {
-                        Err(( "No known encoder", field ))
+                    // Headers which aren't in the table can only be encoded as is
+                    field => match value {
+                        MmsHeaderValue::Bytes(mut b) => {
+                            let mut header_bytes: Vec<u8> = field.into();
+                            header_bytes.append(&mut b);
+                            Ok(header_bytes)
+                        }
+                        _ => Err(("No known encoder", field)),
                     }
             }?;
             Ok(bytes)
@@ -68,15 +50,161 @@ macro_rules! encode_header_field_builder {
     }
 }
 
+fn encode_expiry(v: ExpiryField) -> Vec<u8> {
+    let buf = match v {
+        ExpiryField::Absolute(date) => {
+            let mut buf = vec![128];
+            buf.append(&mut encode_date_value(date));
+            buf
+        }
+        ExpiryField::Relative(delay) => {
+            let mut buf = vec![129];
+            buf.append(&mut encode_integer_value(delay.as_secs()));
+            buf
+        }
+    };
+    value_length(buf)
+}
+
+fn encode_token<T: Into<u8>>(v: T) -> Vec<u8> {
+    vec![v.into()]
+}
+
+fn encode_previously_sent_by(v: PreviouslySentBy) -> Vec<u8> {
+    let mut buf = encode_integer_value(v.forward_count);
+    buf.append(&mut encode_encoded_string(v.address.to_string(), None));
+    value_length(buf)
+}
+
+fn encode_previously_sent_date(v: PreviouslySentDate) -> Vec<u8> {
+    let mut buf = encode_integer_value(v.forward_count);
+    buf.append(&mut encode_date_value(v.date));
+    value_length(buf)
+}
+
+fn encode_mm_flags(v: MMFlagsField) -> Vec<u8> {
+    let (token, keyword) = match v {
+        MMFlagsField::Add(k) => (128, k),
+        MMFlagsField::Remove(k) => (129, k),
+        MMFlagsField::Filter(k) => (130, k),
+    };
+    let mut buf = vec![token];
+    buf.append(&mut encode_encoded_string(keyword, None));
+    value_length(buf)
+}
+
+fn encode_mbox_quantity(v: MboxQuantityField) -> Vec<u8> {
+    let (token, quantity) = match v {
+        MboxQuantityField::Messages(q) => (128, q),
+        MboxQuantityField::Size(q) => (129, q),
+    };
+    let mut buf = vec![token];
+    buf.append(&mut encode_integer_value(quantity));
+    value_length(buf)
+}
+
+fn encode_element_descriptor(
+    v: ElementDescriptor,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut buf = encode_string(v.content_reference);
+
+    for (name, value) in v.parameters {
+        // Type is the only well known parameter name
+        if name == "type" {
+            let content_type = value
+                .parse()
+                .map_err(|_| "Invalid content type in element descriptor")?;
+            buf.append(&mut encode_short_integer(0x02)?);
+            buf.append(&mut encode_content_type(content_type));
+        } else {
+            buf.append(&mut encode_string(name));
+            buf.append(&mut encode_string(value));
+        }
+    }
+
+    Ok(value_length(buf))
+}
+
+/// Encode a header, with the subject encoded in `charset` instead of as a
+/// Text-string or UTF-8
+pub(crate) fn encode_header_field_in_charset(
+    field: MmsHeader,
+    value: MmsHeaderValue,
+    charset: Option<Charset>,
+) -> Result<Vec<u8>, EncodeError> {
+    match (field, value) {
+        (MmsHeader::Subject, MmsHeaderValue::String(v)) => {
+            let mut header_bytes: Vec<u8> = MmsHeader::Subject.into();
+            header_bytes.append(&mut encode_encoded_string(v, charset));
+            Ok(header_bytes)
+        }
+        (field, value) => encode_header_field(field, value),
+    }
+}
+
+/// Encode a value from a M-Delete.conf or M-Mbox-Delete.conf, which is
+/// prefixed with the index of the message it refers to, this mirrors
+/// parser::mms_header::parse_delete_conf_field
+fn encode_indexed(
+    field: &MmsHeader,
+    value: &MmsHeaderValue,
+) -> Option<Vec<u8>> {
+    let (index, mut value_bytes) = match (field, value) {
+        (
+            MmsHeader::XMmsResponseStatus,
+            MmsHeaderValue::IndexedResponseStatus(v),
+        ) => (v.index, encode_token(v.value)),
+        (MmsHeader::XMmsResponseText, MmsHeaderValue::IndexedString(v)) => {
+            (v.index, encode_encoded_string(v.value.clone(), None))
+        }
+        (MmsHeader::XMmsContentLocation, MmsHeaderValue::IndexedString(v)) => {
+            (v.index, encode_string(v.value.clone()))
+        }
+        _ => return None,
+    };
+
+    let mut buf = encode_integer_value(index);
+    buf.append(&mut value_bytes);
+    Some(value_length(buf))
+}
+
+// Every header's encoder mirrors it's parser in parser::mms_header
 encode_header_field_builder! {
-    // TODO: The following fields are required in order to encode a send request
-    // Cc, Bcc
-    // ContentType
-    XMmsMessageType as MessageTypeField => |v: crate::types::mms_header::MessageTypeField| Ok(encode_byte(v.into())),
-    XMmsTransactionId as String => |v| Ok(encode_string(v)),
+    AdditionalHeaders as Bytes => Ok,
+    Bcc as Address => |v: Address| Ok(encode_encoded_string(v.to_string(), None)),
+    Cc as Address => |v: Address| Ok(encode_encoded_string(v.to_string(), None)),
+    Content as Bytes => Ok,
+    ContentType as ContentType => |v| Ok(encode_content_type(v)),
+    Date as SystemTime => |v| Ok(encode_date_value(v)),
+    From as FromField => |v| Ok(
+        match v {
+            mms_header::FromField::Address(addr) => encode_address(addr),
+            mms_header::FromField::InsertAddress => value_length(vec![129])
+        }
+    ),
+    MessageID as String => |v| Ok(encode_string(v)),
+    Subject as String => |v| Ok(encode_encoded_string(v, None)),
+    To as Address => |v: Address| Ok(encode_encoded_string(v.to_string(), None)),
+    XMmsAdaptationAllowed as YesNoField => |v| Ok(encode_token(v)),
+    XMmsApplicID as String => |v| Ok(encode_string(v)),
+    XMmsAttributes as MmsHeader => |v: MmsHeader| Ok(v.into()),
+    XMmsAuxApplicInfo as String => |v| Ok(encode_string(v)),
+    XMmsCancelID as String => |v| Ok(encode_string(v)),
+    XMmsCancelStatus as CancelStatusField => |v| Ok(encode_token(v)),
+    XMmsContentClass as ContentClassField => |v| Ok(encode_token(v)),
+    XMmsContentLocation as String => |v| Ok(encode_string(v)),
+    XMmsDRMContent as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsDeliveryReport as YesNoField => |v| Ok(encode_token(v)),
+    XMmsDeliveryTime as ExpiryField => |v| Ok(encode_expiry(v)),
+    XMmsDistributionIndicator as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsElementDescriptor as ElementDescriptor => encode_element_descriptor,
+    XMmsExpiry as ExpiryField => |v| Ok(encode_expiry(v)),
+    XMmsLimit as LongUint => |v| Ok(encode_integer_value(v)),
+    XMmsMMFlags as MMFlagsField => |v| Ok(encode_mm_flags(v)),
+    XMmsMMState as MMStateField => |v| Ok(encode_token(v)),
     XMmsMMSVersion as ShortUint => |v| Ok(encode_short_integer(v)?),
-    XMmsDeliveryReport as Bool => |v| Ok(vec![encode_bool(v)]),
-    XMmsReadReport as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsMboxQuotas as MboxQuantityField => |v| Ok(encode_mbox_quantity(v)),
+    XMmsMboxTotals as MboxQuantityField => |v| Ok(encode_mbox_quantity(v)),
     XMmsMessageClass as ClassIdentifier => |v: crate::types::mms_header::ClassIdentifier| Ok(
         // TODO: Move move this block somewhere else
         match v {
@@ -89,13 +217,36 @@ encode_header_field_builder! {
             ClassIdentifier::Other(s) => encode_string(s)
         }
     ),
-    From as FromField => |v| Ok(
-        match v {
-            mms_header::FromField::Address(addr) => encode_address(addr),
-            mms_header::FromField::InsertAddress => value_length(vec![129])
-        }
-    ),
-    To as String => |v| Ok(encode_string(v)),
-    Subject as String => |v| Ok(encode_string(v)),
-    ContentType as ContentType => |v| Ok(encode_content_type(v)),
+    XMmsMessageCount as LongUint => |v| Ok(encode_integer_value(v)),
+    XMmsMessageSize as LongUint => |v| Ok(encode_long_integer(v)),
+    XMmsMessageType as MessageTypeField => |v: crate::types::mms_header::MessageTypeField| Ok(encode_byte(v.into())),
+    XMmsPreviouslySentBy as PreviouslySentBy => |v| Ok(encode_previously_sent_by(v)),
+    XMmsPreviouslySentDate as PreviouslySentDate => |v| Ok(encode_previously_sent_date(v)),
+    XMmsPriority as PriorityField => |v| Ok(encode_token(v)),
+    XMmsQuotas as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsReadReport as YesNoField => |v| Ok(encode_token(v)),
+    XMmsReadStatus as ReadStatusField => |v| Ok(encode_token(v)),
+    XMmsRecommendedRetrievalMode as RetrievalModeField => |v| Ok(encode_token(v)),
+    XMmsRecommendedRetrievalModeText as String => |v| Ok(encode_encoded_string(v, None)),
+    XMmsReplaceID as String => |v| Ok(encode_string(v)),
+    XMmsReplyApplicID as String => |v| Ok(encode_string(v)),
+    XMmsReplyCharging as ReplyChargingField => |v| Ok(encode_token(v)),
+    XMmsReplyChargingDeadline as ExpiryField => |v| Ok(encode_expiry(v)),
+    XMmsReplyChargingID as String => |v| Ok(encode_string(v)),
+    XMmsReplyChargingSize as LongUint => |v| Ok(encode_long_integer(v)),
+    XMmsReportAllowed as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsResponseStatus as ResponseStatusField => |v| Ok(encode_token(v)),
+    XMmsResponseText as String => |v| Ok(encode_encoded_string(v, None)),
+    XMmsRetrieveStatus as RetrieveStatusField => |v| Ok(encode_token(v)),
+    XMmsRetrieveText as String => |v| Ok(encode_encoded_string(v, None)),
+    XMmsSenderVisibility as SenderVisibilityField => |v| Ok(encode_token(v)),
+    XMmsStart as LongUint => |v| Ok(encode_integer_value(v)),
+    XMmsStatus as StatusField => |v| Ok(encode_token(v)),
+    XMmsStatusText as String => |v| Ok(encode_encoded_string(v, None)),
+    XMmsStore as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsStoreStatus as StoreStatusField => |v| Ok(encode_token(v)),
+    XMmsStoreStatusText as String => |v| Ok(encode_encoded_string(v, None)),
+    XMmsStored as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsTotals as Bool => |v| Ok(vec![encode_bool(v)]),
+    XMmsTransactionId as String => |v| Ok(encode_string(v)),
 }
@@ -1,14 +1,22 @@
-use super::{encode_content_type, encode_uintvar, encode_wap_headers};
-use crate::types::{message_header::MessageHeader, multipart::MultiPartItem};
+use super::{
+    encode_content_type, encode_uintvar, encode_wap_headers, EncodeError,
+};
+use crate::{
+    types::{
+        message_header::MessageHeader,
+        multipart::{MultiPartBody, MultiPartItem, PartBody},
+    },
+    Charset,
+};
 
 use mime::Mime;
 
 pub trait EncodableBody {
     fn content_type(&self) -> &mime::Mime;
-    fn encode(self) -> Vec<u8>;
+    fn encode(self) -> Result<Vec<u8>, EncodeError>;
 }
 
-pub trait Item: Into<MultiPartItem> {
+pub trait Item: Into<MultiPartItem<'static>> {
     // type SharedState;
 
     fn multipart_type(&self) -> mime::Mime;
@@ -25,8 +33,8 @@ impl EncodableBody for (mime::Mime, Vec<u8>) {
         &self.0
     }
 
-    fn encode(self) -> Vec<u8> {
-        self.1
+    fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        Ok(self.1)
     }
 }
 
@@ -57,36 +65,42 @@ impl<I: Item> EncoderBuilder<I> {
         self.parts.push(part);
     }
 
-    // TODO: Use Result instead of Option
-    /// Finalize builder into a type that can be encoded
-    pub fn build(mut self) -> Option<MultiPartEncoder> {
+    /// Finalize builder into a type that can be encoded, this fails if there
+    /// are no parts
+    pub fn build(mut self) -> Result<MultiPartEncoder, EncodeError> {
         I::fianilize_encoder(&mut self);
 
         if let Some(part) = self.layout {
             self.parts.insert(0, part);
         }
 
-        let content_type = self.parts.first().unwrap().multipart_type();
+        let content_type = self
+            .parts
+            .first()
+            .ok_or("A multipart body needs at least one part")?
+            .multipart_type();
 
-        Some(MultiPartEncoder {
-            parts: self.parts.drain(..).map(|i| i.into()).collect(),
+        Ok(MultiPartEncoder {
+            body: MultiPartBody::new(
+                self.parts.drain(..).map(|i| i.into()).collect(),
+            ),
             content_type,
         })
     }
 }
 
 pub struct MixedItem {
-    item: MultiPartItem,
+    item: MultiPartItem<'static>,
 }
 
 impl MixedItem {
-    pub fn new(item: MultiPartItem) -> Self {
+    pub fn new(item: MultiPartItem<'static>) -> Self {
         Self { item }
     }
 }
 
-impl Into<MultiPartItem> for MixedItem {
-    fn into(self) -> MultiPartItem {
+impl Into<MultiPartItem<'static>> for MixedItem {
+    fn into(self) -> MultiPartItem<'static> {
         self.item
     }
 }
@@ -98,7 +112,7 @@ impl Item for MixedItem {
 }
 
 pub struct RelatedBodyPart {
-    item: MultiPartItem,
+    item: MultiPartItem<'static>,
     id: String,
 }
 
@@ -115,15 +129,56 @@ impl RelatedBodyPart {
                 MessageHeader::ContentId(id.clone()),
                 MessageHeader::ContentLocation(location.clone()),
             ],
-            body,
+            body: body.into(),
         };
 
         Self { item, id }
     }
+
+    /// A text/plain part, encoded in `charset` with a matching charset
+    /// parameter. Without a charset ascii text is sent as US-ASCII, and
+    /// anything else as UTF-8, which is also used if `text` can't be
+    /// represented in `charset`
+    pub fn text(
+        text: &str,
+        charset: Option<Charset>,
+        id: String,
+        location: String,
+    ) -> Self {
+        let charset = charset.unwrap_or(if text.is_ascii() {
+            Charset::US_ASCII
+        } else {
+            Charset::UTF_8
+        });
+
+        let (charset, body) = match charset.encode(text) {
+            Ok(body) => (charset, body),
+            Err(_) => (Charset::UTF_8, text.as_bytes().to_vec()),
+        };
+
+        let content_type = format!("text/plain; charset={}", charset.name())
+            .parse()
+            .unwrap();
+
+        Self::new(content_type, body, id, location)
+    }
+
+    /// A part which is itself a multipart body, such as a
+    /// multipart.alternative with the same text in different formats
+    pub fn multipart(
+        body: MultiPartEncoder,
+        id: String,
+        location: String,
+    ) -> Self {
+        let mut part =
+            Self::new(body.content_type.clone(), Vec::new(), id, location);
+        part.item.body = PartBody::MultiPart(body.body);
+        part
+    }
 }
 
-impl Into<MultiPartItem> for RelatedBodyPart {
-    fn into(self) -> MultiPartItem {
+impl Into<MultiPartItem<'static>> for RelatedBodyPart {
+    fn into(self) -> MultiPartItem<'static> {
         self.item
     }
 }
@@ -158,32 +213,111 @@ impl Item for RelatedBodyPart {
 }
 
 pub struct MultiPartEncoder {
-    parts: Vec<MultiPartItem>,
+    body: MultiPartBody<'static>,
     content_type: Mime,
 }
 
+impl MultiPartEncoder {
+    /// Turn the body into a part, so it can be nested in another multipart
+    /// body
+    pub fn into_item(
+        self,
+        headers: Vec<MessageHeader>,
+    ) -> MultiPartItem<'static> {
+        MultiPartItem {
+            content_type: self.content_type,
+            headers,
+            body: PartBody::MultiPart(self.body),
+        }
+    }
+}
+
 impl EncodableBody for MultiPartEncoder {
     fn content_type(&self) -> &Mime {
         &self.content_type
     }
 
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::new();
+    fn encode(self) -> Result<Vec<u8>, EncodeError> {
+        encode_multipart_body(self.body)
+    }
+}
+
+pub(crate) fn encode_multipart_body(
+    body: MultiPartBody<'_>,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut buf = Vec::new();
+
+    if body.has_num_entries {
+        buf.append(&mut encode_uintvar(body.parts.len() as u64));
+    }
+    for part in body.parts {
+        let mut headers = encode_wap_headers(part.headers)?;
+        let mut content_type = encode_content_type(part.content_type);
+        let mut body = match part.body {
+            PartBody::Data(data) => data.into_owned(),
+            PartBody::MultiPart(body) => encode_multipart_body(body)?,
+        };
+
+        buf.append(&mut encode_uintvar(
+            (headers.len() + content_type.len()) as u64,
+        ));
+        buf.append(&mut encode_uintvar(body.len() as u64));
+        buf.append(&mut content_type);
+        buf.append(&mut headers);
+        buf.append(&mut body);
+    }
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_multipart_round_trip() {
+        let mut alternative = EncoderBuilder::new();
+        alternative.part(MixedItem::new(MultiPartItem {
+            content_type: mime::TEXT_PLAIN,
+            headers: vec![],
+            body: b"text".to_vec().into(),
+        }));
+        let alternative = alternative.build().unwrap();
+
+        let mut mixed = EncoderBuilder::new();
+        mixed.part(MixedItem::new(alternative.into_item(vec![])));
+        let encoded = mixed.build().unwrap().encode().unwrap();
+
+        let decoded = crate::parse_multipart_body(&encoded).unwrap();
+        let leaves = decoded.leaves();
+
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].body.data(), Some(&b"text"[..]));
+    }
+
+    #[test]
+    fn num_entries_form_is_kept() {
+        let part = MultiPartItem {
+            content_type: mime::TEXT_PLAIN,
+            headers: vec![],
+            body: b"text".to_vec().into(),
+        };
 
-        buf.append(&mut encode_uintvar(self.parts.len() as u64));
-        for mut part in self.parts {
-            let mut headers = encode_wap_headers(part.headers);
-            let mut content_type = encode_content_type(part.content_type);
+        for body in [
+            MultiPartBody::new(vec![part.clone()]),
+            MultiPartBody::without_num_entries(vec![part]),
+        ] {
+            let has_num_entries = body.has_num_entries;
+            let encoded = encode_multipart_body(body).unwrap();
+            let decoded = crate::parse_multipart_body(&encoded).unwrap();
 
-            buf.append(&mut encode_uintvar(
-                (headers.len() + content_type.len()) as u64,
-            ));
-            buf.append(&mut encode_uintvar(part.body.len() as u64));
-            buf.append(&mut content_type);
-            buf.append(&mut headers);
-            buf.append(&mut part.body);
+            assert_eq!(decoded.has_num_entries, has_num_entries);
+            assert_eq!(encode_multipart_body(decoded).unwrap(), encoded);
         }
+    }
 
-        buf
+    #[test]
+    fn empty_body_is_an_error() {
+        assert!(EncoderBuilder::<MixedItem>::new().build().is_err());
     }
 }
@@ -0,0 +1,255 @@
+use crate::types::MmsHeader;
+
+use nom::error::{ContextError, ErrorKind};
+use std::{borrow::Cow, error::Error, fmt};
+
+/// An error encountered while parsing wap or mms data
+///
+/// Unlike the errors produced by nom this doesn't borrow the input, so it can
+/// be returned from functions which own their data, or stored in an
+/// `anyhow::Error`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The data ended before a complete value could be read
+    Incomplete {
+        offset: usize,
+        header: Option<MmsHeader>,
+    },
+    /// The data contained a value which isn't allowed by the spec
+    Invalid {
+        offset: usize,
+        header: Option<MmsHeader>,
+        reason: String,
+    },
+    /// A wap push contained something other than an mms message
+    UnsupportedContentType(mime::Mime),
+    /// A header required by the message type wasn't present
+    MissingHeader(MmsHeader),
+    /// A header's value wasn't the type expected for that header
+    UnexpectedValue(MmsHeader),
+}
+
+impl ParseError {
+    /// Position in the input where the error happened
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Incomplete { offset, .. } | Self::Invalid { offset, .. } => {
+                Some(*offset)
+            }
+            Self::UnsupportedContentType(_)
+            | Self::MissingHeader(_)
+            | Self::UnexpectedValue(_) => None,
+        }
+    }
+
+    /// The mms header which was being parsed when the error happened, if any
+    pub fn header(&self) -> Option<&MmsHeader> {
+        match self {
+            Self::Incomplete { header, .. } | Self::Invalid { header, .. } => {
+                header.as_ref()
+            }
+            Self::MissingHeader(header) | Self::UnexpectedValue(header) => {
+                Some(header)
+            }
+            Self::UnsupportedContentType(_) => None,
+        }
+    }
+
+    /// Move the offset of the error forward by `by` bytes, used when the
+    /// parsed data didn't start at the beginning of the pdu
+    pub(crate) fn shift(mut self, by: usize) -> Self {
+        match &mut self {
+            Self::Incomplete { offset, .. } | Self::Invalid { offset, .. } => {
+                *offset += by
+            }
+            Self::UnsupportedContentType(_)
+            | Self::MissingHeader(_)
+            | Self::UnexpectedValue(_) => {}
+        }
+        self
+    }
+
+    /// Convert a nom error produced by one of this crate's parsers into a
+    /// `ParseError`, `data` must be the input which was passed to the parser
+    pub(crate) fn from_nom(
+        data: &[u8],
+        err: nom::Err<InputError<&[u8]>>,
+    ) -> Self {
+        match err {
+            nom::Err::Incomplete(_) => Self::Incomplete {
+                offset: data.len(),
+                header: None,
+            },
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                e.into_parse_error(data)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Incomplete { offset, header } => {
+                write!(f, "Unexpected end of data at byte {}", offset)?;
+                if let Some(header) = header {
+                    write!(f, " while parsing {:?}", header)?;
+                }
+                Ok(())
+            }
+            Self::Invalid {
+                offset,
+                header,
+                reason,
+            } => {
+                write!(f, "{} at byte {}", reason, offset)?;
+                if let Some(header) = header {
+                    write!(f, " while parsing {:?}", header)?;
+                }
+                Ok(())
+            }
+            Self::UnsupportedContentType(c) => {
+                write!(f, "Unable to parse data with content type {}", c)
+            }
+            Self::MissingHeader(header) => {
+                write!(f, "Required header {:?} is missing", header)
+            }
+            Self::UnexpectedValue(header) => {
+                write!(f, "Header {:?} has an unexpected value", header)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// The error type used by the parsers internally, it keeps a reference to the
+/// input it failed on so the offset can be calculated once the parser returns
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InputError<I> {
+    pub(crate) input: I,
+    pub(crate) kind: ErrorKind,
+    pub(crate) reason: Option<Cow<'static, str>>,
+    pub(crate) header: Option<MmsHeader>,
+}
+
+pub(crate) type IResult<I, O> = nom::IResult<I, O, InputError<I>>;
+
+impl<I> InputError<I> {
+    pub(crate) fn new(input: I, reason: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            input,
+            kind: ErrorKind::Verify,
+            reason: Some(reason.into()),
+            header: None,
+        }
+    }
+
+    /// Record the header which was being parsed, unless one has already been
+    /// recorded by an inner parser
+    pub(crate) fn in_header(mut self, header: &MmsHeader) -> Self {
+        if self.header.is_none() {
+            self.header = Some(header.clone());
+        }
+        self
+    }
+}
+
+impl InputError<&[u8]> {
+    fn into_parse_error(self, data: &[u8]) -> ParseError {
+        let start = data.as_ptr() as usize;
+        let position = self.input.as_ptr() as usize;
+
+        // Errors are sometimes created from a sub slice of the original data,
+        // or an empty slice which isn't part of it at all
+        let offset = if position >= start && position <= start + data.len() {
+            position - start
+        } else {
+            data.len()
+        };
+
+        match (self.kind, self.reason) {
+            (ErrorKind::Eof, None) => ParseError::Incomplete {
+                offset,
+                header: self.header,
+            },
+            (kind, reason) => ParseError::Invalid {
+                offset,
+                header: self.header,
+                reason: reason.map(|r| r.into_owned()).unwrap_or_else(|| {
+                    format!("Parser failed: {}", kind.description())
+                }),
+            },
+        }
+    }
+}
+
+impl<I> nom::error::ParseError<I> for InputError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        Self {
+            input,
+            kind,
+            reason: None,
+            header: None,
+        }
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I> ContextError<I> for InputError<I> {
+    fn add_context(_: I, ctx: &'static str, mut other: Self) -> Self {
+        if other.reason.is_none() {
+            other.reason = Some(ctx.into());
+        }
+        other
+    }
+}
+
+/// Make a parser fail with a human readable reason
+pub(crate) fn invalid<I, O>(
+    input: I,
+    reason: impl Into<Cow<'static, str>>,
+) -> IResult<I, O> {
+    Err(nom::Err::Error(InputError::new(input, reason)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::error::ParseError as _;
+
+    #[test]
+    fn offset_of_sub_slice() {
+        let data = [1u8, 2, 3, 4];
+        let err = InputError::new(&data[2..], "bad value");
+
+        assert_eq!(
+            err.into_parse_error(&data),
+            ParseError::Invalid {
+                offset: 2,
+                header: None,
+                reason: "bad value".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn eof_is_incomplete() {
+        let data = [1u8, 2, 3, 4];
+        let err = nom::Err::Error(InputError::from_error_kind(
+            &data[4..],
+            ErrorKind::Eof,
+        ));
+
+        assert_eq!(
+            ParseError::from_nom(&data, err),
+            ParseError::Incomplete {
+                offset: 4,
+                header: None
+            }
+        );
+    }
+}
@@ -1,11 +1,17 @@
+mod charset;
 pub mod encoder;
+mod error;
 mod helpers;
 mod parser;
 mod pdu;
+mod stream;
 pub mod types;
 
-pub use parser::parse_multipart_body;
+pub use charset::Charset;
+pub use error::ParseError;
+pub use parser::{parse_multipart_body, parse_multipart_body_strict};
 pub use pdu::*;
+pub use stream::{read_mms_pdu_headers, MultiPartReader, PartReader};
 
 #[macro_use]
 extern crate nom;
@@ -13,7 +19,8 @@ extern crate nom;
 #[macro_use]
 extern crate derivative;
 
-use parser::{header_item, parse_content_type, uintvar};
+use error::{IResult, InputError};
+use parser::{parse_content_type, uintvar, wap_headers};
 use types::{MessageHeader, PduType, VndWapMmsMessage, Wap};
 
 use nom::{
@@ -21,7 +28,6 @@ use nom::{
     combinator::{all_consuming, map_parser},
     do_parse, named,
     number::complete::be_u8,
-    IResult,
 };
 
 // TODO: This is public for creating message headers for encoding, it may make
@@ -30,30 +36,25 @@ pub use ordered_multimap::ListOrderedMultimap as MultiMap;
 
 pub const MMS_VERSION: u8 = 18;
 
-impl Wap {
-    // TODO: Replace Option with Result
-    pub fn parse_body(&self) -> Option<VndWapMmsMessage> {
+impl Wap<'_> {
+    pub fn parse_body(&self) -> Result<VndWapMmsMessage<'_>, ParseError> {
         match self.content_type.essence_str() {
-            "application/vnd.wap.mms-message" => {
-                let split = match split_header_fields(&*self.data) {
-                    Ok((remainder, s)) => {
-                        if remainder.len() > 0 {
-                            return None;
-                        }
-                        s
-                    }
-                    Err(_) => return None,
-                };
-                let parsed = parse_header_fields(&split);
-                Some(VndWapMmsMessage::new(parsed))
-            }
-            _ => None,
+            "application/vnd.wap.mms-message" => parse_mms_pdu(&self.data),
+            _ => Err(ParseError::UnsupportedContentType(
+                self.content_type.clone(),
+            )),
         }
     }
 }
 
+pub fn parse_wap_push(d: &[u8]) -> Result<Wap<'_>, ParseError> {
+    wap_push(d)
+        .map(|(_, wap)| wap)
+        .map_err(|e| ParseError::from_nom(d, e))
+}
+
 // TODO: Put this somewhere else so I don't have to look at it
-named!(pub parse_wap_push<Wap>,
+named!(wap_push<&[u8], Wap<'_>, InputError<&[u8]>>,
     do_parse!(
         // TODO: This field should ONLY be red in "connectionless PDUs" it could cause problems
         transaction_id: be_u8 >>
@@ -65,14 +66,13 @@ named!(pub parse_wap_push<Wap>,
                 message_type: PduType::from(message_type),
                 content_type: message_headers.0,
                 headers: message_headers.1,
-                data,
+                data: data.into(),
         })
     )
 );
 
-fn take_all(d: &[u8]) -> IResult<&[u8], Vec<u8>> {
-    let e: &[u8] = &[];
-    Ok((e, d.to_vec()))
+fn take_all(d: &[u8]) -> IResult<&[u8], &[u8]> {
+    Ok((&d[d.len()..], d))
 }
 
 fn parse_message_headers(
@@ -90,24 +90,13 @@ fn content_type(d: &[u8]) -> IResult<&[u8], mime::Mime> {
     map_parser(pdu::take_field, parse_content_type)(d)
 }
 
-pub fn wap_header_item(d: &[u8]) -> IResult<&[u8], MessageHeader> {
-    // This can be a string, handle that case
-    let (d, header_byte) = take(1u8)(d)?;
-    let header_byte = header_byte[0] & 0x7F;
-    let (d, raw_field) = take_field(d)?;
-
-    let (_, parsed_field) = header_item(header_byte, raw_field)?;
-
-    Ok((d, parsed_field))
-}
-
 // TODO: this should return a content type struct or a string rather than a &[u8]
 named!(
-    pub message_headers<(mime::Mime, Vec<MessageHeader>)>,
+    pub(crate) message_headers<&[u8], (mime::Mime, Vec<MessageHeader>), InputError<&[u8]>>,
     do_parse!(
         take!(0)
             >> content_type: content_type
-            >> headers: many0!(wap_header_item)
+            >> headers: wap_headers
             >> (content_type, headers)
     )
 );
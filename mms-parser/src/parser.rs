@@ -6,15 +6,17 @@ mod uintvar;
 
 pub use content_type::*;
 pub use message_header::*;
-pub use multipart::parse_multipart_body;
+pub(crate) use multipart::part_headers;
+pub use multipart::{parse_multipart_body, parse_multipart_body_strict};
 pub use uintvar::*;
 
+use crate::error::{invalid, IResult};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take, take_till, take_till1},
     combinator::opt,
-    IResult,
 };
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn take_text_string(d: &[u8]) -> IResult<&[u8], &[u8]> {
     let (dc, val) = take_till(|c| c == 0)(d)?;
@@ -22,31 +24,25 @@ pub fn take_text_string(d: &[u8]) -> IResult<&[u8], &[u8]> {
     Ok((dc, &d[..val.len() + 1]))
 }
 
-pub fn parse_text_string(d: &[u8]) -> IResult<&[u8], String> {
-    let (d, val) = take_till1(|c| c == '\u{0}' as u8)(d)?;
+pub fn parse_text_string(input: &[u8]) -> IResult<&[u8], String> {
+    let (d, val) = take_till1(|c| c == b'\0')(input)?;
 
     let (d, _) = opt(tag("\x00"))(d)?;
 
     if val[0] >= 128 {
-        return Err(nom::Err::Error(nom::error::Error::new(
-            d,
-            nom::error::ErrorKind::Satisfy,
-        )));
+        return invalid(input, "Text-string starts with a character above 127");
     }
 
-    let val = if val[0] == 0x7f && val[1] >= 128 {
+    let val = if val[0] == 0x7f && val.len() > 1 && val[1] >= 128 {
         &val[1..]
     } else {
         val
     };
 
     let val = match super::helpers::u8_to_string(val) {
-        Ok(v) => Ok(v),
-        Err(_) => Err(nom::Err::Error(nom::error::Error::new(
-            d,
-            nom::error::ErrorKind::Satisfy,
-        ))),
-    }?;
+        Ok(v) => v,
+        Err(_) => return invalid(input, "Text-string isn't valid utf-8"),
+    };
 
     Ok((d, val))
 }
@@ -64,25 +60,22 @@ pub fn parse_short_integer(d: &[u8]) -> IResult<&[u8], u8> {
     let bit = bit[0];
 
     if bit & 0x80 == 0 {
-        return Err(nom::Err::Error(nom::error::Error::new(
-            d,
-            nom::error::ErrorKind::Satisfy,
-        )));
+        return invalid(d, "Short-integer doesn't have it's 8th bit set");
     }
 
     Ok((r, bit & 0x7F))
 }
 
-pub fn parse_long_integer(d: &[u8]) -> IResult<&[u8], u64> {
-    let (d, len) = take(1u8)(d)?;
+pub fn parse_long_integer(input: &[u8]) -> IResult<&[u8], u64> {
+    let (d, len) = take(1u8)(input)?;
     let len = len[0];
 
     if len > 30 {
-        return Err(nom::Err::Error(nom::error::Error::new(
-            &[],
-            nom::error::ErrorKind::Satisfy,
-        )));
+        return invalid(input, "Long-integer is longer than 30 bytes");
     };
+    if len > 8 {
+        return invalid(input, "Long-integer is too large to fit in a u64");
+    }
 
     let (d, bytes) = take(len)(d)?;
 
@@ -109,30 +102,64 @@ pub fn parse_integer_value(d: &[u8]) -> IResult<&[u8], u64> {
     alt((short_integer_u64, parse_long_integer))(d)
 }
 
+/// Date-value, seconds since the unix epoch
+pub fn parse_date_value(d: &[u8]) -> IResult<&[u8], SystemTime> {
+    let (r, seconds) = parse_long_integer(d)?;
+    match UNIX_EPOCH.checked_add(Duration::from_secs(seconds)) {
+        Some(date) => Ok((r, date)),
+        None => invalid(d, format!("Date {} is out of range", seconds)),
+    }
+}
+
+/// Delta-seconds-value, a number of seconds
+pub fn parse_delta_seconds_value(d: &[u8]) -> IResult<&[u8], Duration> {
+    let (d, seconds) = parse_integer_value(d)?;
+    Ok((d, Duration::from_secs(seconds)))
+}
+
 pub fn parse_value_length(data: &[u8]) -> IResult<&[u8], u64> {
     let (remainder, l1) = take(1u8)(data)?;
 
     match l1[0] {
         0..=30 => Ok((remainder, l1[0] as u64)),
         31 => uintvar(remainder),
-        _ => Err(nom::Err::Error(nom::error::Error::new(
-            data,
-            nom::error::ErrorKind::Satisfy,
-        ))),
+        _ => invalid(data, "Value-length must start with a byte below 32"),
     }
 }
 
-fn parse_value_length_charset_string(d: &[u8]) -> IResult<&[u8], String> {
-    // TODO: handle charsets, and use the value length, instead of hoping the
-    // string ends in null
-    let (d, _len) = parse_value_length(d)?;
-    let (d, _charset_id) = take(1u8)(d)?;
+/// Well-known-charset, `None` means any charset
+pub fn parse_well_known_charset(d: &[u8]) -> IResult<&[u8], Option<u64>> {
+    match d.first() {
+        Some(128) => Ok((&d[1..], None)),
+        _ => {
+            let (d, charset) = parse_integer_value(d)?;
+            Ok((d, Some(charset)))
+        }
+    }
+}
+
+fn parse_value_length_charset_string(input: &[u8]) -> IResult<&[u8], String> {
+    let (d, len) = parse_value_length(input)?;
+    let (d, value) = take(len)(d)?;
+    let (text, charset) = parse_well_known_charset(value)?;
+
+    let text = match crate::charset::decode(
+        charset.unwrap_or(crate::Charset::UTF_8.mib_enum()),
+        text,
+    ) {
+        Ok(t) => t,
+        Err(e) => return invalid(input, e),
+    };
 
-    parse_text_string(d)
+    Ok((d, text))
 }
 
 pub fn parse_encoded_string_value(d: &[u8]) -> IResult<&[u8], String> {
-    alt((parse_text_string, parse_value_length_charset_string))(d)
+    // A Value-length starts with a byte below 32, which a Text-string can't
+    match d.first() {
+        Some(0..=31) => parse_value_length_charset_string(d),
+        _ => parse_text_string(d),
+    }
 }
 
 #[cfg(test)]
@@ -213,4 +240,43 @@ mod test {
     fn invalid_value_length() {
         parse_value_length(&[32]).unwrap_err();
     }
+
+    #[test]
+    fn utf_16_encoded_string_value() {
+        let input = [
+            0x09, 0x02, 0x03, 0xF7, 0xFE, 0xFF, 0x00, b'h', 0x00, b'i', 42,
+        ];
+        let (r, s) = parse_encoded_string_value(&input).unwrap();
+
+        assert_eq!(s, "hi");
+        assert_eq!(r, &[42]);
+    }
+
+    #[test]
+    fn windows_1252_encoded_string_value() {
+        let input =
+            [0x09, 0x02, 0x08, 0xCC, 0xA9, b'2', b'0', b'2', b'0', 0x00];
+        let (r, s) = parse_encoded_string_value(&input).unwrap();
+
+        assert_eq!(s, "\u{A9}2020");
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn big5_encoded_string_value() {
+        let input = [0x06, 0x02, 0x07, 0xEA, 0xA4, 0xA4, 0x00];
+        let (r, s) = parse_encoded_string_value(&input).unwrap();
+
+        assert_eq!(s, "\u{4E2D}");
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn encoded_string_value_is_bounded_by_length() {
+        let input = [0x03, 0xEA, b'a', b'b', b'c', 0];
+        let (r, s) = parse_encoded_string_value(&input).unwrap();
+
+        assert_eq!(s, "ab");
+        assert_eq!(r, &[b'c', 0]);
+    }
 }
@@ -1,57 +1,143 @@
 use super::*;
-use crate::types::content_type_codes::CONTENT_TYPE_CODES;
+use crate::types::{
+    content_type_codes::CONTENT_TYPE_CODES,
+    parameter_codes::{ParameterValue, PARAMETER_CODES},
+};
+use log::debug;
 use mime::Mime;
-use nom::IResult;
-
-fn well_known_charset(chr_set: u64) -> Option<String> {
-    // From http://www.iana.org/assignments/character-sets/character-sets.xhtml
-    Some(
-        match chr_set {
-            3 => "US-ASCII",
-            4 => "ISO-8859-1",
-            106 => "UTF-8",
-            _ => return None,
-        }
-        .to_string(),
-    )
+
+fn parse_q_value(d: &[u8]) -> IResult<&[u8], String> {
+    let (d, q) = uintvar(d)?;
+
+    let q = match q {
+        1..=100 => (q - 1) as f64 / 100.0,
+        101..=1099 => (q - 100) as f64 / 1000.0,
+        _ => return invalid(d, format!("Q-value {} is out of range", q)),
+    };
+
+    Ok((d, q.to_string()))
 }
 
-// TODO: This will probably need to be shortened with a macro, there are about
-// 30 of these
-fn parse_well_known_parameter(d: &[u8], param: u64) -> IResult<&[u8], String> {
-    // From wap-230-wsp table 38
-    match param {
-        0x01 => {
-            if d[0] == 128 {
-                Ok((&d[1..], "charset=\"*\"".to_string()))
+fn parse_version_value(d: &[u8]) -> IResult<&[u8], String> {
+    match parse_short_integer(d) {
+        Ok((d, v)) => {
+            let major = (v >> 4) & 0x07;
+            let minor = v & 0x0F;
+
+            let version = if minor == 0x0F {
+                major.to_string()
             } else {
-                let (d, chr_set) = parse_integer_value(d)?;
-                Ok((
-                    d,
-                    format!(
-                        "charset=\"{}\"",
-                        well_known_charset(chr_set)
-                            .unwrap_or(format!("{}", chr_set))
-                    ),
-                ))
+                format!("{}.{}", major, minor)
+            };
+            Ok((d, version))
+        }
+        Err(_) => parse_text_string(d),
+    }
+}
+
+/// Text-value, which is either No-value, Token-text, or Quoted-string
+fn parse_text_value(d: &[u8]) -> IResult<&[u8], String> {
+    match d.first() {
+        Some(0) => Ok((&d[1..], String::new())),
+        Some(b'"') => parse_quoted_string(d),
+        _ => parse_text_string(d),
+    }
+}
+
+fn parse_parameter_value(
+    d: &[u8],
+    value: ParameterValue,
+) -> IResult<&[u8], String> {
+    use ParameterValue::*;
+
+    match value {
+        QValue => parse_q_value(d),
+        WellKnownCharset => {
+            let (d, charset) = parse_well_known_charset(d)?;
+            let charset = match charset {
+                None => "*".to_string(),
+                Some(c) => crate::charset::charset_name(c)
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| c.to_string()),
+            };
+            Ok((d, charset))
+        }
+        VersionValue => parse_version_value(d),
+        IntegerValue => {
+            let (d, v) = parse_integer_value(d)?;
+            Ok((d, v.to_string()))
+        }
+        TextString => parse_text_string(d),
+        FieldName => match d.first() {
+            Some(128..=255) => {
+                let (d, v) = parse_short_integer(d)?;
+                Ok((d, v.to_string()))
             }
+            _ => parse_text_string(d),
+        },
+        ShortInteger => {
+            let (d, v) = parse_short_integer(d)?;
+            Ok((d, v.to_string()))
         }
-        0x05 => {
-            let (d, value) = parse_text_string(d)?;
-            Ok((d, format!("name=\"{}\"", value)))
+        ConstrainedEncoding => parse_constrained_encoding(d),
+        DeltaSecondsValue => {
+            let (d, v) = parse_delta_seconds_value(d)?;
+            Ok((d, v.as_secs().to_string()))
         }
-        0x09 => {
-            let (d, value) = parse_constrained_encoding(d)?;
-            Ok((d, format!("type=\"{}\"", value)))
+        NoValue => {
+            let (d, _) = tag("\0")(d)?;
+            Ok((d, String::new()))
         }
-        0x0A => {
-            let (d, value) = parse_text_string(d)?;
-            Ok((d, format!("start=\"{}\"", value)))
+        TextValue => parse_text_value(d),
+        // Dates are kept as seconds since the unix epoch, since there isn't
+        // any other way to represent them in a mime type
+        DateValue => {
+            let (d, v) = parse_long_integer(d)?;
+            Ok((d, v.to_string()))
         }
-        i => unimplemented!(
-            "Cannot parse well known parameter {:#04X} in content_type",
-            i
-        ),
+    }
+}
+
+fn parse_well_known_parameter(d: &[u8]) -> IResult<&[u8], (String, String)> {
+    let (r, code) = parse_integer_value(d)?;
+
+    let (name, value) = match PARAMETER_CODES
+        .iter()
+        .find(|(c, _, _)| u64::from(*c) == code)
+    {
+        Some((_, name, value)) => (name, value),
+        None => {
+            return invalid(
+                d,
+                format!("Unknown well known parameter {:#04X}", code),
+            )
+        }
+    };
+
+    let (r, value) = parse_parameter_value(r, *value)?;
+    Ok((r, (name.to_string(), value)))
+}
+
+/// Untyped-parameter, a Token-text name followed by either an Integer-value
+/// or a Text-value
+fn parse_untyped_parameter(d: &[u8]) -> IResult<&[u8], (String, String)> {
+    let (d, name) = parse_text_string(d)?;
+
+    let (d, value) = match d.first() {
+        Some(1..=30) | Some(128..=255) => {
+            let (d, v) = parse_integer_value(d)?;
+            (d, v.to_string())
+        }
+        _ => parse_text_value(d)?,
+    };
+
+    Ok((d, (name, value)))
+}
+
+pub(crate) fn parse_parameter(d: &[u8]) -> IResult<&[u8], (String, String)> {
+    match d.first() {
+        Some(32..=127) => parse_untyped_parameter(d),
+        _ => parse_well_known_parameter(d),
     }
 }
 
@@ -76,22 +162,34 @@ fn parse_content_type_general_form(d: &[u8]) -> IResult<&[u8], String> {
     let (d, len) = parse_value_length(d)?;
     let (d, header) = take(len)(d)?;
 
-    let (mut params_data, media) = match header[0] >= 128 {
-        true => parse_well_known_content_type(header),
-        false => parse_text_string(header),
+    let (mut params_data, media) = match header.first() {
+        Some(b) if *b >= 128 => parse_well_known_content_type(header),
+        Some(_) => parse_text_string(header),
+        None => invalid(header, "Content-type-value is empty"),
     }?;
 
     let mut params = Vec::new();
 
-    while params_data.len() > 0 {
-        match parse_integer_value(params_data) {
-            Ok((r, p)) => {
-                let (r, param) = parse_well_known_parameter(r, p)?;
-                params_data = r;
-                params.push(param);
-            }
-            Err(_) => unimplemented!(),
+    while !params_data.is_empty() {
+        let (r, (name, value)) = parse_parameter(params_data)?;
+        params_data = r;
+
+        // Mime types can't have empty parameters
+        if value.is_empty() {
+            debug!("Dropping content type parameter {} without a value", name);
+            continue;
+        }
+        // The mime crate can't represent every value, such as ones containing
+        // a `"`, so those are dropped rather than the whole content type
+        let param = format!("{}=\"{}\"", name, value);
+        if format!("{}; {}", media, param).parse::<Mime>().is_err() {
+            debug!(
+                "Dropping content type parameter {} with an invalid value",
+                name
+            );
+            continue;
         }
+        params.push(param);
     }
 
     let ct = if params.len() >= 1 {
@@ -105,13 +203,19 @@ fn parse_content_type_general_form(d: &[u8]) -> IResult<&[u8], String> {
 }
 
 // see wap-230-wsp-20010705-a.pdf section 8.4.2.24
-pub fn parse_content_type(d: &[u8]) -> IResult<&[u8], Mime> {
-    let (d, c) = match d[0] {
-        0..=31 => parse_content_type_general_form(d),
-        32..=255 => parse_constrained_encoding(d),
+pub fn parse_content_type(input: &[u8]) -> IResult<&[u8], Mime> {
+    let (d, c) = match input.first() {
+        Some(0..=31) => parse_content_type_general_form(input),
+        Some(32..=255) => parse_constrained_encoding(input),
+        None => invalid(input, "Content-type-value is empty"),
     }?;
 
-    let mime_type: Mime = c.parse().unwrap();
+    let mime_type: Mime = match c.parse() {
+        Ok(m) => m,
+        Err(_) => {
+            return invalid(input, format!("{:?} isn't a valid mime type", c))
+        }
+    };
     Ok((d, mime_type))
 }
 
@@ -123,6 +227,18 @@ mod test {
         mime.parse::<mime::Mime>().unwrap()
     }
 
+    #[test]
+    fn invalid_parameters_are_dropped() {
+        let mut data = vec![0x0F, 0x83];
+        data.extend_from_slice(b"name\0a\"b\0x\0ok\0");
+
+        let (_, content_type) = parse_content_type(&data).unwrap();
+
+        assert_eq!(content_type.essence_str(), "text/plain");
+        assert_eq!(content_type.get_param("name"), None);
+        assert_eq!(content_type.get_param("x").unwrap(), "ok");
+    }
+
     #[test]
     fn content_string() {
         let (r, c) = parse_content_type("text/plain".as_bytes()).unwrap();
@@ -173,4 +289,34 @@ mod test {
         assert_eq!(r, &[]);
         assert_eq!(c, "unusual/type; charset=*/*")
     }
+
+    #[test]
+    fn filename_param() {
+        let (r, c) = parse_content_type(b"\x0C\x83\x98photo.jpg\0").unwrap();
+
+        assert_eq!(r, &[]);
+        assert_eq!(c.get_param("filename").unwrap(), "photo.jpg");
+    }
+
+    #[test]
+    fn q_and_date_params() {
+        let (_, c) = parse_content_type(&[
+            0x07, 0x83, 0x80, 0x33, 0x93, 0x02, 0x01, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(c.get_param("q").unwrap(), "0.5");
+        assert_eq!(c.get_param("creation-date").unwrap(), "256");
+    }
+
+    #[test]
+    fn untyped_params() {
+        let (_, c) = parse_content_type(
+            b"\x19\x83x-custom\0\"a b\0x-size\0\x02\x01\x00",
+        )
+        .unwrap();
+
+        assert_eq!(c.get_param("x-custom").unwrap(), "a b");
+        assert_eq!(c.get_param("x-size").unwrap(), "256");
+    }
 }
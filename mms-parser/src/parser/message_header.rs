@@ -1,36 +1,305 @@
-use crate::types::{MessageHeader, MessageHeader::*};
-use nom::{bytes::complete::take, IResult};
+use crate::{
+    error::{invalid, IResult},
+    types::{
+        message_header::{
+            AcceptCharsetValue, CharsetValue, ContentDisposition, LanguageValue,
+        },
+        MessageHeader,
+        MessageHeader::*,
+    },
+};
+use log::debug;
+use nom::bytes::complete::take;
 
-use super::{parse_quoted_string, parse_text_string};
+use super::{
+    content_type::parse_parameter, parse_date_value, parse_integer_value,
+    parse_quoted_string, parse_short_integer, parse_text_string,
+    parse_value_length, uintvar,
+};
 
+fn take_value_length(d: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (d, len) = parse_value_length(d)?;
+    take(len)(d)
+}
+
+fn parse_content_encoding(d: &[u8]) -> IResult<&[u8], String> {
+    match d.first() {
+        Some(128) => Ok((&d[1..], "gzip".to_string())),
+        Some(129) => Ok((&d[1..], "compress".to_string())),
+        Some(130) => Ok((&d[1..], "deflate".to_string())),
+        _ => parse_text_string(d),
+    }
+}
+
+fn parse_language(d: &[u8]) -> IResult<&[u8], LanguageValue> {
+    match d.first() {
+        Some(128) => Ok((&d[1..], LanguageValue::Any)),
+        Some(32..=127) => {
+            let (d, name) = parse_text_string(d)?;
+            Ok((d, LanguageValue::Name(name)))
+        }
+        _ => {
+            let (d, code) = parse_integer_value(d)?;
+            Ok((d, LanguageValue::Code(code)))
+        }
+    }
+}
+
+fn parse_charset(d: &[u8]) -> IResult<&[u8], CharsetValue> {
+    match d.first() {
+        Some(128) => Ok((&d[1..], CharsetValue::Any)),
+        Some(32..=127) => {
+            let (d, name) = parse_text_string(d)?;
+            Ok((d, CharsetValue::Name(name)))
+        }
+        _ => {
+            let (d, code) = parse_integer_value(d)?;
+            Ok((d, CharsetValue::Code(code)))
+        }
+    }
+}
+
+/// Q-value, in thousandths
+fn parse_q_value(d: &[u8]) -> IResult<&[u8], u16> {
+    let (r, q) = uintvar(d)?;
+    match q {
+        1..=100 => Ok((r, (q as u16 - 1) * 10)),
+        101..=1099 => Ok((r, q as u16 - 100)),
+        _ => invalid(d, "Q-value must be between 1 and 1099"),
+    }
+}
+
+/// Accept-charset-value, either a charset on it's own, or the general form
+/// with a Value-length and optional Q-value
+fn parse_accept_charset(d: &[u8]) -> IResult<&[u8], AcceptCharsetValue> {
+    let (d, (charset, quality)) = match d.first() {
+        Some(0..=31) => {
+            let (d, value) = take_value_length(d)?;
+            let (value, charset) = parse_charset(value)?;
+            let quality = match value {
+                [] => None,
+                value => Some(parse_q_value(value)?.1),
+            };
+            (d, (charset, quality))
+        }
+        _ => {
+            let (d, charset) = parse_charset(d)?;
+            (d, (charset, None))
+        }
+    };
+
+    Ok((d, AcceptCharsetValue { charset, quality }))
+}
+
+fn parse_content_md5(d: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (d, digest) = take_value_length(d)?;
+    Ok((d, digest.to_vec()))
+}
+
+fn parse_content_disposition(d: &[u8]) -> IResult<&[u8], ContentDisposition> {
+    let (d, value) = take_value_length(d)?;
+
+    let (mut params, disposition) = match value.first() {
+        Some(128) => (&value[1..], "form-data".to_string()),
+        Some(129) => (&value[1..], "attachment".to_string()),
+        Some(130) => (&value[1..], "inline".to_string()),
+        _ => parse_text_string(value)?,
+    };
+
+    let mut parameters = Vec::new();
+    while !params.is_empty() {
+        let (r, param) = parse_parameter(params)?;
+        params = r;
+        parameters.push(param);
+    }
+
+    Ok((
+        d,
+        ContentDisposition {
+            disposition,
+            parameters,
+        },
+    ))
+}
+
+fn parse_encoding_version(d: &[u8]) -> IResult<&[u8], String> {
+    let (d, v) = parse_short_integer(d)?;
+    let major = (v >> 4) & 0x07;
+    let minor = v & 0x0F;
+
+    let version = if minor == 0x0F {
+        major.to_string()
+    } else {
+        format!("{}.{}", major, minor)
+    };
+    Ok((d, version))
+}
+
+fn parse_usize(d: &[u8]) -> IResult<&[u8], usize> {
+    let (r, v) = parse_integer_value(d)?;
+    Ok((r, v as usize))
+}
+
+// From wap-230-wsp table 39, headers which can't be represented by
+// MessageHeader are kept as UnknownHeader
 pub fn header_item(header_byte: u8, d: &[u8]) -> IResult<&[u8], MessageHeader> {
-    let (d, header_item) = match header_byte {
+    let parsed = match header_byte {
         // Accept Charset
-        0x01 => {
-            let (d, c) = take(1u8)(d)?;
-            (d, AcceptCharset(c[0]))
+        0x01 => parse_accept_charset(d).map(|(d, v)| (d, AcceptCharset(v))),
+        0x0A => parse_text_string(d).map(|(d, v)| (d, ContentBase(v))),
+        0x0B => parse_content_encoding(d).map(|(d, v)| (d, ContentEncoding(v))),
+        0x0C => parse_language(d).map(|(d, v)| (d, ContentLanguage(v))),
+        0x0D => parse_usize(d).map(|(d, v)| (d, ContentLength(v))),
+        0x0E => parse_text_string(d).map(|(d, v)| (d, ContentLocation(v))),
+        0x0F => parse_content_md5(d).map(|(d, v)| (d, ContentMd5(v))),
+        0x12 => parse_date_value(d).map(|(d, v)| (d, Date(v))),
+        0x13 => parse_text_string(d).map(|(d, v)| (d, Etag(v))),
+        0x14 => parse_date_value(d).map(|(d, v)| (d, Expires(v))),
+        0x1D => parse_date_value(d).map(|(d, v)| (d, LastModified(v))),
+        0x2E | 0x45 => parse_content_disposition(d)
+            .map(|(d, v)| (d, ContentDisposition(v))),
+        0x2F => parse_usize(d).map(|(d, v)| (d, XWapApplicationId(v))),
+        0x30 => parse_text_string(d).map(|(d, v)| (d, XWapContentUri(v))),
+        0x31 => parse_text_string(d).map(|(d, v)| (d, XWapInitiatorUri(v))),
+        0x34 => parse_short_integer(d).map(|(d, v)| (d, PushFlag(v))),
+        0x40 => parse_quoted_string(d).map(|(d, v)| (d, ContentId(v))),
+        0x43 => parse_encoding_version(d).map(|(d, v)| (d, EncodingVersion(v))),
+        b => return Ok((&d[d.len()..], UnknownHeader((b, d.to_vec())))),
+    };
+
+    // Values which use a form this crate doesn't understand are kept as is, so
+    // they can still be re-encoded
+    match parsed {
+        Ok((r, header)) if r.is_empty() => Ok((r, header)),
+        _ => {
+            debug!("Keeping header {:#04X} unparsed", header_byte);
+            Ok((&d[d.len()..], UnknownHeader((header_byte, d.to_vec()))))
         }
-        // Content Length
-        0x0D => {
-            let (d, l) = take(1u8)(d)?;
-            (d, ContentLength(l[0] as usize))
+    }
+}
+
+/// Parse a single header, which is either a well known header in the current
+/// code page, an application header, or a shift to another code page
+fn wap_header(d: &[u8], code_page: u8) -> IResult<&[u8], MessageHeader> {
+    match d.first() {
+        Some(0x7F) => {
+            let (d, page) = take(2u8)(d)?;
+            Ok((d, ShiftCodePage(page[1])))
         }
-        0x0E => {
-            let (d, v) = parse_text_string(d)?;
-            (d, ContentLocation(v))
+        Some(page @ 1..=31) => Ok((&d[1..], ShiftCodePage(*page))),
+        Some(32..=126) => {
+            let (d, name) = parse_text_string(d)?;
+            let (d, value) = match d.first() {
+                Some(0) => (&d[1..], String::new()),
+                _ => parse_text_string(d)?,
+            };
+            Ok((d, ApplicationHeader((name, value))))
         }
-        0x40 => {
-            let (d, v) = parse_quoted_string(d)?;
-            (d, ContentId(v))
+        Some(b @ 128..=255) => {
+            let code = b & 0x7F;
+            let (d, value) = crate::pdu::take_field(&d[1..])?;
+
+            if code_page == 1 {
+                let (_, header) = header_item(code, value)?;
+                Ok((d, header))
+            } else {
+                Ok((d, UnknownHeader((code, value.to_vec()))))
+            }
         }
-        // XWapApplicationId
-        0x2F => {
-            let (d, id) = take(1u8)(d)?;
-            (d, XWapApplicationId(id[0] as usize))
+        _ => invalid(d, "Expected a header"),
+    }
+}
+
+/// Parse headers until the end of `d`, keeping track of code page shifts
+pub(crate) fn wap_headers(d: &[u8]) -> IResult<&[u8], Vec<MessageHeader>> {
+    let mut headers = Vec::new();
+    let mut code_page = 1;
+    let mut d = d;
+
+    while !d.is_empty() {
+        let (r, header) = wap_header(d, code_page)?;
+        if let ShiftCodePage(page) = header {
+            code_page = page;
         }
+        headers.push(header);
+        d = r;
+    }
 
-        b => (d, UnknownHeader((b, d.to_vec()))),
-    };
+    Ok((d, headers))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    Ok((d, header_item))
+    #[test]
+    fn content_disposition_with_filename() {
+        let (_, headers) = wap_headers(b"\xAE\x0C\x81\x98photo.jpg\0").unwrap();
+
+        match &headers[..] {
+            [ContentDisposition(d)] => {
+                assert_eq!(d.disposition, "attachment");
+                assert_eq!(d.filename(), Some("photo.jpg"));
+            }
+            h => panic!("Unexpected headers {:?}", h),
+        }
+    }
+
+    #[test]
+    fn accept_charset() {
+        let (_, headers) = wap_headers(&[
+            0x81, 0xEA, 0x81, 0x80, 0x81, 0x04, 0x02, 0x03, 0xF7, 0x65,
+        ])
+        .unwrap();
+
+        match &headers[..] {
+            [AcceptCharset(utf_8), AcceptCharset(any), AcceptCharset(utf_16)] =>
+            {
+                assert_eq!(utf_8.charset, CharsetValue::Code(106));
+                assert_eq!(utf_8.quality, None);
+                assert_eq!(any.charset, CharsetValue::Any);
+                assert_eq!(utf_16.charset, CharsetValue::Code(1015));
+                assert_eq!(utf_16.quality, Some(1));
+            }
+            h => panic!("Unexpected headers {:?}", h),
+        }
+    }
+
+    #[test]
+    fn long_content_length() {
+        let (_, headers) = wap_headers(&[0x8D, 0x02, 0x01, 0x00]).unwrap();
+
+        match &headers[..] {
+            [ContentLength(l)] => assert_eq!(*l, 256),
+            h => panic!("Unexpected headers {:?}", h),
+        }
+    }
+
+    #[test]
+    fn application_header() {
+        let (_, headers) = wap_headers(b"X-Custom\0value\0").unwrap();
+
+        match &headers[..] {
+            [ApplicationHeader((name, value))] => {
+                assert_eq!(name, "X-Custom");
+                assert_eq!(value, "value");
+            }
+            h => panic!("Unexpected headers {:?}", h),
+        }
+    }
+
+    #[test]
+    fn other_code_pages_are_unknown() {
+        let (_, headers) =
+            wap_headers(&[0x7F, 0x02, 0x8D, 0x81, 0x01, 0x8D, 0x81]).unwrap();
+
+        match &headers[..] {
+            [ShiftCodePage(2), UnknownHeader((0x0D, v)), ShiftCodePage(1), l] =>
+            {
+                assert_eq!(v, &[0x81]);
+                assert!(matches!(l, ContentLength(1)));
+            }
+            h => panic!("Unexpected headers {:?}", h),
+        }
+    }
 }
@@ -1,39 +1,180 @@
+use crate::error::{invalid, IResult};
 use crate::parser::*;
 use crate::types::mms_header::MmsHeader::*;
 use crate::types::mms_header::*;
+use crate::types::Address;
 
 use log::debug;
 use mime::Mime;
-use nom::{bytes::complete::take, IResult};
-use std::convert::TryFrom;
+use nom::bytes::complete::take;
+use std::time::SystemTime;
 
-pub fn parse_enum_class(d: &[u8]) -> IResult<&[u8], ClassIdentifier> {
-    let (d, class) = take(1u8)(d)?;
+pub fn parse_enum_class(input: &[u8]) -> IResult<&[u8], ClassIdentifier> {
+    let (d, class) = take(1u8)(input)?;
 
     let class = match class[0] {
         128 => ClassIdentifier::Personal,
         129 => ClassIdentifier::Advertisment,
         130 => ClassIdentifier::Informational,
         131 => ClassIdentifier::Auto,
-        _ => {
-            return Err(nom::Err::Error(nom::error::Error::new(
-                d,
-                nom::error::ErrorKind::Satisfy,
-            )))
-        }
+        c => return invalid(input, format!("Unknown message class {}", c)),
     };
 
     Ok((d, class))
 }
 
-pub fn parse_string_class(d: &[u8]) -> IResult<&[u8], ClassIdentifier> {
-    let (d, class) =
-        nom::bytes::complete::take_till1(|c| c == '\u{0}' as u8)(d)?;
-    let class = crate::helpers::u8_to_string(class).unwrap();
+pub fn parse_string_class(input: &[u8]) -> IResult<&[u8], ClassIdentifier> {
+    let (d, class) = nom::bytes::complete::take_till1(|c| c == b'\0')(input)?;
+    let class = match crate::helpers::u8_to_string(class) {
+        Ok(c) => c,
+        Err(_) => return invalid(input, "Message class isn't valid utf-8"),
+    };
 
     Ok((d, ClassIdentifier::Other(class)))
 }
 
+fn parse_bool(input: &[u8]) -> IResult<&[u8], bool> {
+    let (d, value) = take(1u8)(input)?;
+    match value[0] {
+        128 => Ok((d, true)),
+        129 => Ok((d, false)),
+        v => invalid(input, format!("Expected a yes or no token, found {}", v)),
+    }
+}
+
+/// Take a single token, such as the value of X-Mms-Priority
+fn parse_token<T: std::convert::From<u8>>(d: &[u8]) -> IResult<&[u8], T> {
+    let (d, token) = take(1u8)(d)?;
+    Ok((d, T::from(token[0])))
+}
+
+/// Take the rest of the field as is
+fn take_bytes(d: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    Ok((&d[d.len()..], d.to_vec()))
+}
+
+/// Take a Value-length and the data it covers
+fn take_value_length(d: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (d, len) = parse_value_length(d)?;
+    take(len)(d)
+}
+
+pub fn parse_expiry_field(d: &[u8]) -> IResult<&[u8], ExpiryField> {
+    let (d, value) = take_value_length(d)?;
+    let (data, token) = take(1u8)(value)?;
+
+    let field = match token[0] {
+        128 => ExpiryField::Absolute(parse_date_value(data)?.1),
+        129 => ExpiryField::Relative(parse_delta_seconds_value(data)?.1),
+        t => {
+            return invalid(value, format!("Unknown time token {}", t));
+        }
+    };
+
+    Ok((d, field))
+}
+
+/// Parse an address, which is an Encoded-string-value. Carriers send
+/// addresses which aren't valid, such as alphanumeric sender ids, so those are
+/// kept as [`Address::Unknown`]
+pub fn parse_address(input: &[u8]) -> IResult<&[u8], Address> {
+    let (d, address) = parse_encoded_string_value(input)?;
+    Ok((d, Address::parse_lenient(&address)))
+}
+
+pub fn parse_previously_sent_by(d: &[u8]) -> IResult<&[u8], PreviouslySentBy> {
+    let (d, value) = take_value_length(d)?;
+    let (value, forward_count) = parse_integer_value(value)?;
+    let (_, address) = parse_address(value)?;
+
+    Ok((
+        d,
+        PreviouslySentBy {
+            forward_count,
+            address,
+        },
+    ))
+}
+
+pub fn parse_previously_sent_date(
+    d: &[u8],
+) -> IResult<&[u8], PreviouslySentDate> {
+    let (d, value) = take_value_length(d)?;
+    let (value, forward_count) = parse_integer_value(value)?;
+    let (_, date) = parse_date_value(value)?;
+
+    Ok((
+        d,
+        PreviouslySentDate {
+            forward_count,
+            date,
+        },
+    ))
+}
+
+pub fn parse_mm_flags(d: &[u8]) -> IResult<&[u8], MMFlagsField> {
+    let (d, value) = take_value_length(d)?;
+    let (data, token) = take(1u8)(value)?;
+    let (_, keyword) = parse_encoded_string_value(data)?;
+
+    let flags = match token[0] {
+        128 => MMFlagsField::Add(keyword),
+        129 => MMFlagsField::Remove(keyword),
+        130 => MMFlagsField::Filter(keyword),
+        t => return invalid(value, format!("Unknown MM-Flags token {}", t)),
+    };
+
+    Ok((d, flags))
+}
+
+pub fn parse_mbox_quantity(d: &[u8]) -> IResult<&[u8], MboxQuantityField> {
+    let (d, value) = take_value_length(d)?;
+    let (data, token) = take(1u8)(value)?;
+    let (_, quantity) = parse_integer_value(data)?;
+
+    let quantity = match token[0] {
+        128 => MboxQuantityField::Messages(quantity),
+        129 => MboxQuantityField::Size(quantity),
+        t => {
+            return invalid(value, format!("Unknown mbox quantity token {}", t))
+        }
+    };
+
+    Ok((d, quantity))
+}
+
+fn element_descriptor_parameter(d: &[u8]) -> IResult<&[u8], (String, String)> {
+    // Type is the only well known parameter name
+    if let Ok((d, 0x02)) = parse_short_integer(d) {
+        let (d, value) = parse_content_type(d)?;
+        return Ok((d, ("type".to_string(), value.to_string())));
+    }
+
+    let (d, name) = parse_text_string(d)?;
+    let (d, value) = nom::branch::alt((
+        parse_text_string,
+        nom::combinator::map(parse_short_integer, |i| i.to_string()),
+    ))(d)?;
+
+    Ok((d, (name, value)))
+}
+
+pub fn parse_element_descriptor(d: &[u8]) -> IResult<&[u8], ElementDescriptor> {
+    let (d, value) = take_value_length(d)?;
+    let (value, content_reference) = parse_text_string(value)?;
+    let (_, parameters) = nom::combinator::all_consuming(nom::multi::many0(
+        element_descriptor_parameter,
+    ))(value)?;
+
+    Ok((
+        d,
+        ElementDescriptor {
+            content_reference,
+            parameters,
+        },
+    ))
+}
+
 macro_rules! parse_header_field_builder {
     ($($field_name:ident as $type:ty => $parse:expr),+$(,)*) => {
         pub(crate) fn parse_header_field(field: MmsHeader, d: &[u8]) -> IResult<&[u8], MmsHeaderValue> {
@@ -46,10 +187,6 @@ macro_rules! parse_header_field_builder {
                     }
                 )*
                     field => {
-                        // TODO: This doesn't need to panic, just return an
-                        // error
-                        // nom::error::Error::from_external_error is probably what is needed for
-                        // this
                         debug!("A parser for field `{:?}` isn't implemented yet, falling back to bytes", field);
                         let value = MmsHeaderValue::from(d.to_vec());
                         Ok((&[], value))
@@ -58,132 +195,343 @@ macro_rules! parse_header_field_builder {
         }
     }
 }
-// NOTE: I haven't properly tested parsers which are commented out, they should work, but I'd like
-// to check first
 parse_header_field_builder! {
+    AdditionalHeaders as Bytes => |d| take_bytes(d),
+    Bcc as Address => |d| parse_address(d),
+    Cc as Address => |d| parse_address(d),
+    Content as Bytes => |d| take_bytes(d),
     // TODO: I haven't been able to properly parse content-type yet
     ContentType as Mime => |d| parse_content_type(d),
-    Date as LongUint => |d| parse_long_integer(d),
-    From as String => |d| {
-        let (d, len) = parse_value_length(d)?;
-        let (d, value) = take(len)(d)?;
+    Date as SystemTime => |d| parse_date_value(d),
+    From as FromField => |d| -> IResult<&[u8], FromField> {
+        let (d, value) = take_value_length(d)?;
 
         let (data, token) = take(1u8)(value)?;
         let token = token[0];
 
         match token {
-            128 => Ok((
-                    d,
-                    parse_encoded_string_value(data)?.1,
-            )),
-            129 => Ok((data, "<insert>".to_string())),
-            _ => {
-                //error
-                panic!("Unexpected Token: {:?}", token);
-            }
+            128 => Ok((d, FromField::Address(parse_address(data)?.1))),
+            129 => Ok((d, FromField::InsertAddress)),
+            _ => invalid(value, format!("Unexpected token {} in From", token)),
         }
     },
     MessageID as String => |d| parse_text_string(d),
     Subject as String => |d| parse_encoded_string_value(d),
-    To as String => |d| parse_encoded_string_value(d),
-    //XMmsAdaptationAllowed, Bool => |d| -> IResult<&[u8], bool> {
-    //    let (d, allowed) = take(1u8)(d)?;
-    //    match allowed[0] {
-    //        128 => Ok((d, true)),
-    //        129 => Ok((d, false)),
-    //        // TODO: Have a recoverable error type, which means that an unknown value was parsed,
-    //        // but it is okay to keep going
-    //        // Yes when testing this on a mms message reviced on t-mobile there was a value of 115
-    //        // which I don't know how to interpret, so I'm just ignoreing it
-    //        _ => Ok((d, false))
-    //            // _ => unimplemented!()
-    //    }
-    //},
-    XMmsContentLocation as String => |d| parse_text_string(d),
-    XMmsDeliveryReport as Bool => |d| -> IResult<&[u8], bool> {
-        // TODO: parse bool logic seems to be duplicated, perhaps write a macro for this match?
-        let (d, report) = take(1u8)(d)?;
-        match report[0] {
-            128 => Ok((d, true)),
-            129 => Ok((d, false)),
-            _ => unimplemented!() // TODO: just return an error
-        }
+    To as Address => |d| parse_address(d),
+    // When testing this on a mms message reviced on t-mobile there was a
+    // value of 115 which I don't know how to interpret, it's kept as
+    // YesNoField::Unknown
+    XMmsAdaptationAllowed as YesNoField => |d| parse_token(d),
+    XMmsApplicID as String => |d| parse_text_string(d),
+    XMmsAttributes as MmsHeader => |d| -> IResult<&[u8], MmsHeader> {
+        let (d, header) = parse_short_integer(d)?;
+        Ok((d, MmsHeader::from(header)))
     },
-    //XMmsExpiry, ExpiryField => |d| {
-    //    let (d, len) = parse_value_length(d)?;
-    //    let (d, value) = take(len)(d)?;
-
-    //    let (value, token) = take(1u8)(value)?;
-
-    //    let field = match token[0] {
-    //        128 => {
-    //            let (_, unix_time) = parse_long_integer(value)?;
-    //            ExpiryField::Absolute(unix_time)
-    //        }
-    //        129 => {
-    //            let (_, time_delta) = parse_long_integer(value)?;
-    //            ExpiryField::Relative(time_delta)
-    //        }
-    //        _ => {
-    //            // TODO: Not valid, return an error
-    //            unimplemented!()
-    //        }
-    //    };
-
-    //    Ok((d, field))
-    //},
-    //XMmsLimit, LongUint => |d| parse_integer_value(d),
+    XMmsAuxApplicInfo as String => |d| parse_text_string(d),
+    XMmsCancelID as String => |d| parse_text_string(d),
+    XMmsCancelStatus as CancelStatusField => |d| parse_token(d),
+    XMmsContentClass as ContentClassField => |d| parse_token(d),
+    XMmsContentLocation as String => |d| parse_text_string(d),
+    XMmsDRMContent as Bool => |d| parse_bool(d),
+    XMmsDeliveryReport as YesNoField => |d| parse_token(d),
+    XMmsDeliveryTime as ExpiryField => |d| parse_expiry_field(d),
+    XMmsDistributionIndicator as Bool => |d| parse_bool(d),
+    XMmsElementDescriptor as ElementDescriptor => |d| parse_element_descriptor(d),
+    XMmsExpiry as ExpiryField => |d| parse_expiry_field(d),
+    XMmsLimit as LongUint => |d| parse_integer_value(d),
+    XMmsMMFlags as MMFlagsField => |d| parse_mm_flags(d),
+    XMmsMMState as MMStateField => |d| parse_token(d),
     XMmsMMSVersion as ShortUint => |d| parse_short_integer(d),
+    XMmsMboxQuotas as MboxQuantityField => |d| parse_mbox_quantity(d),
+    XMmsMboxTotals as MboxQuantityField => |d| parse_mbox_quantity(d),
     XMmsMessageClass as ClassIdentifier => |d| nom::branch::alt((parse_enum_class, parse_string_class))(d),
+    XMmsMessageCount as LongUint => |d| parse_integer_value(d),
     XMmsMessageSize as LongUint => |d| parse_long_integer(d),
-    XMmsMessageType as MessageTypeField => |d| -> IResult<&[u8], MessageTypeField> {
-        let (d, message_type) = take(1u8)(d)?;
-        Ok((
-                d,
-                // TODO: return error insetad of unwraping
-                MessageTypeField::try_from(message_type[0]).unwrap(),
-        ))
-    },
-    XMmsPriority as ShortUint => |d| -> IResult<&[u8], u8> { // TODO: Use enum instead of u8
-                let (d, priority) = take(1u8)(d)?;
-                let priority = match priority[0] {
-                    128 => 1,
-                    129 => 2,
-                    130 => 3,
-                    _ => unimplemented!()
-                };
-                Ok((d, priority))
-    },
-    XMmsReadReport as Bool => |d| -> IResult<&[u8], bool> {
-        let (d, report) = take(1u8)(d)?;
-        match report[0] {
-            128 => Ok((d, true)),
-            129 => Ok((d, false)),
-            _ => unimplemented!() // TODO: just return an error
-        }
-    },
-    // TODO: XMmsResponseText has a different format when the pdu type is M-Delete.conf
+    XMmsMessageType as MessageTypeField => |d| parse_token(d),
+    XMmsPreviouslySentBy as PreviouslySentBy => |d| parse_previously_sent_by(d),
+    XMmsPreviouslySentDate as PreviouslySentDate => |d| parse_previously_sent_date(d),
+    XMmsPriority as PriorityField => |d| parse_token(d),
+    XMmsQuotas as Bool => |d| parse_bool(d),
+    XMmsReadReport as YesNoField => |d| parse_token(d),
+    XMmsReadStatus as ReadStatusField => |d| parse_token(d),
+    XMmsRecommendedRetrievalMode as RetrievalModeField => |d| parse_token(d),
+    XMmsRecommendedRetrievalModeText as String => |d| parse_encoded_string_value(d),
+    XMmsReplaceID as String => |d| parse_text_string(d),
+    XMmsReplyApplicID as String => |d| parse_text_string(d),
+    XMmsReplyCharging as ReplyChargingField => |d| parse_token(d),
+    XMmsReplyChargingDeadline as ExpiryField => |d| parse_expiry_field(d),
+    XMmsReplyChargingID as String => |d| parse_text_string(d),
+    XMmsReplyChargingSize as LongUint => |d| parse_long_integer(d),
+    XMmsReportAllowed as Bool => |d| parse_bool(d),
     XMmsResponseText as String => |d| parse_encoded_string_value(d),
-    XMmsRetrieveStatus as RetrieveStatusField => |d| -> IResult<&[u8], RetrieveStatusField> {
-        // TODO: Move this to a try_from function
-        // Also a seemingly successful get on
-        let (d, status) = take(1u8)(d)?;
-
-        let status = match status[0] {
-            128 => RetrieveStatusField::Ok,
-            192 => RetrieveStatusField::ErrorTransientFailure,
-            193 => RetrieveStatusField::ErrorTransientMessageNotFound,
-            194 => RetrieveStatusField::ErrorTransientNetworkProblem,
-            195..=223 => RetrieveStatusField::ErrorTransientFailureOther(status[0]),
-            224 => RetrieveStatusField::ErrorPermanentFailure,
-            225 => RetrieveStatusField::ErrorPermanentServceDenied,
-            226 => RetrieveStatusField::ErrorPermanentMessageNotFound,
-            227 => RetrieveStatusField::ErrorPermanentContentUnsupported,
-            // TODO: This should pontentially be for just 228..255
-            _ => RetrieveStatusField::ErrorPermanentFailureOther(status[0]),
-        };
-        Ok((d, status))
-    },
+    XMmsRetrieveStatus as RetrieveStatusField => |d| parse_token(d),
+    // M-Delete.conf and M-Mbox-Delete.conf use parse_delete_conf_field
+    XMmsResponseStatus as ResponseStatusField => |d| parse_token(d),
+    XMmsRetrieveText as String => |d| parse_encoded_string_value(d),
+    XMmsSenderVisibility as SenderVisibilityField => |d| parse_token(d),
+    XMmsStart as LongUint => |d| parse_integer_value(d),
+    XMmsStatus as StatusField => |d| parse_token(d),
+    XMmsStatusText as String => |d| parse_encoded_string_value(d),
+    XMmsStore as Bool => |d| parse_bool(d),
+    XMmsStoreStatus as StoreStatusField => |d| parse_token(d),
+    XMmsStoreStatusText as String => |d| parse_encoded_string_value(d),
+    XMmsStored as Bool => |d| parse_bool(d),
+    XMmsTotals as Bool => |d| parse_bool(d),
     XMmsTransactionId as String => |d| parse_text_string(d),
     //ImplicitBody, Vec<u8> => |d: &[u8]| -> IResult<&[u8], Vec<u8>> { Ok(( &[], d.to_vec() )) },
 }
+
+/// Take the Value-length and Status-count-value which come before a value
+/// in a M-Delete.conf or M-Mbox-Delete.conf
+fn take_indexed(d: &[u8]) -> IResult<&[u8], (u64, &[u8])> {
+    let (d, value) = take_value_length(d)?;
+    let (value, index) = parse_integer_value(value)?;
+    Ok((d, (index, value)))
+}
+
+/// Parse a header from a M-Delete.conf or M-Mbox-Delete.conf, where
+/// X-Mms-Response-Status, X-Mms-Response-Text and X-Mms-Content-Location are
+/// prefixed with the index of the message they refer to
+pub(crate) fn parse_delete_conf_field(
+    field: MmsHeader,
+    d: &[u8],
+) -> IResult<&[u8], MmsHeaderValue> {
+    match field {
+        XMmsResponseStatus => {
+            let (d, (index, value)) = take_indexed(d)?;
+            let (_, value) = parse_token(value)?;
+            Ok((d, IndexedResponseStatus { index, value }.into()))
+        }
+        XMmsResponseText => {
+            let (d, (index, value)) = take_indexed(d)?;
+            let (_, value) = parse_encoded_string_value(value)?;
+            Ok((d, IndexedString { index, value }.into()))
+        }
+        XMmsContentLocation => {
+            let (d, (index, value)) = take_indexed(d)?;
+            let (_, value) = parse_text_string(value)?;
+            Ok((d, IndexedString { index, value }.into()))
+        }
+        field => parse_header_field(field, d),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn relative_expiry() {
+        let (r, v) = parse_header_field(
+            XMmsExpiry,
+            &[0x06, 0x81, 0x04, 0, 1, 0x51, 0x80],
+        )
+        .unwrap();
+
+        assert_eq!(r, &[]);
+        match v {
+            MmsHeaderValue::ExpiryField(ExpiryField::Relative(t)) => {
+                assert_eq!(t, Duration::from_secs(86400))
+            }
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn absolute_expiry() {
+        let (_, v) = parse_header_field(
+            XMmsExpiry,
+            &[0x06, 0x80, 0x04, 0x5F, 0xC1, 0x5E, 0x00],
+        )
+        .unwrap();
+
+        match v {
+            MmsHeaderValue::ExpiryField(e) => assert_eq!(
+                e,
+                ExpiryField::Absolute(
+                    UNIX_EPOCH + Duration::from_secs(0x5FC15E00)
+                )
+            ),
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn relative_expiry_to_absolute() {
+        let received = UNIX_EPOCH + Duration::from_secs(1000);
+        let expiry = ExpiryField::Relative(Duration::from_secs(60));
+
+        assert_eq!(
+            expiry.to_absolute(received),
+            Some(UNIX_EPOCH + Duration::from_secs(1060))
+        );
+
+        let expiry = ExpiryField::Relative(Duration::from_secs(u64::MAX));
+        assert_eq!(expiry.to_absolute(received), None);
+    }
+
+    #[test]
+    fn alphanumeric_sender_is_kept() {
+        let (_, v) =
+            parse_header_field(From, b"\x14\x80Vodafone/TYPE=PLMN\0").unwrap();
+
+        assert_eq!(
+            v,
+            MmsHeaderValue::FromField(FromField::Address(Address::Unknown(
+                "Vodafone/TYPE=PLMN".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn out_of_range_date_is_invalid() {
+        parse_header_field(
+            Date,
+            &[0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        )
+        .unwrap_err();
+        parse_header_field(
+            XMmsExpiry,
+            &[
+                0x0B, 0x80, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0xFF,
+            ],
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn date() {
+        let (_, v) =
+            parse_header_field(Date, &[0x04, 0x5F, 0xC1, 0x5E, 0x00]).unwrap();
+
+        match v {
+            MmsHeaderValue::SystemTime(t) => {
+                assert_eq!(t, UNIX_EPOCH + Duration::from_secs(0x5FC15E00))
+            }
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn unknown_expiry_token() {
+        parse_header_field(XMmsExpiry, &[0x02, 0x85, 0x81]).unwrap_err();
+    }
+
+    #[test]
+    fn previously_sent_by() {
+        let (_, v) =
+            parse_header_field(XMmsPreviouslySentBy, b"\x06\x82+1234\0")
+                .unwrap();
+
+        match v {
+            MmsHeaderValue::PreviouslySentBy(v) => {
+                assert_eq!(v.forward_count, 2);
+                assert_eq!(v.address, Address::Plmn("+1234".to_string()));
+            }
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn mbox_quota_size() {
+        let (_, v) =
+            parse_header_field(XMmsMboxQuotas, &[0x04, 0x81, 0x02, 0x10, 0x00])
+                .unwrap();
+
+        match v {
+            MmsHeaderValue::MboxQuantityField(MboxQuantityField::Size(s)) => {
+                assert_eq!(s, 0x1000)
+            }
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn element_descriptor_with_type() {
+        let (_, v) = parse_header_field(
+            XMmsElementDescriptor,
+            b"\x0Btext.txt\0\x82\x83",
+        )
+        .unwrap();
+
+        match v {
+            MmsHeaderValue::ElementDescriptor(e) => {
+                assert_eq!(e.content_reference, "text.txt");
+                assert_eq!(
+                    e.parameters,
+                    vec![("type".to_string(), "text/plain".to_string())]
+                );
+            }
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn attributes_header() {
+        let (_, v) = parse_header_field(XMmsAttributes, &[0x96]).unwrap();
+
+        match v {
+            MmsHeaderValue::MmsHeader(h) => assert_eq!(h, Subject),
+            v => panic!("Unexpected value {:?}", v),
+        }
+    }
+
+    #[test]
+    fn response_status() {
+        let status = |b: u8| match parse_header_field(XMmsResponseStatus, &[b])
+        {
+            Ok((_, MmsHeaderValue::ResponseStatusField(s))) => s,
+            v => panic!("Unexpected value {:?}", v),
+        };
+
+        assert_eq!(status(128), ResponseStatusField::Ok);
+        assert!(!status(128).is_transient() && !status(128).is_permanent());
+        assert_eq!(
+            status(195),
+            ResponseStatusField::ErrorTransientNetworkProblem
+        );
+        assert!(status(195).is_transient());
+        assert_eq!(
+            status(226),
+            ResponseStatusField::ErrorPermanentMessageFormatCorrupt
+        );
+        assert!(status(226).is_permanent());
+        assert!(status(210).is_transient());
+        assert!(status(250).is_permanent());
+        assert!(status(132).is_permanent());
+        assert_eq!(status(5), ResponseStatusField::Unknown(5));
+    }
+
+    #[test]
+    fn delivery_status() {
+        let (_, v) = parse_header_field(XMmsStatus, &[0x83]).unwrap();
+        assert_eq!(v, MmsHeaderValue::StatusField(StatusField::Deferred));
+
+        assert!(StatusField::Deferred.is_transient());
+        assert!(StatusField::Rejected.is_permanent());
+        assert!(!StatusField::Retrieved.is_permanent());
+        assert_eq!(StatusField::from(140), StatusField::Unknown(140));
+    }
+
+    #[test]
+    fn unknown_tokens_are_kept() {
+        let (_, v) = parse_header_field(XMmsPriority, &[0x82]).unwrap();
+        assert_eq!(v, MmsHeaderValue::PriorityField(PriorityField::High));
+
+        let (_, v) = parse_header_field(XMmsPriority, &[115]).unwrap();
+        assert_eq!(
+            v,
+            MmsHeaderValue::PriorityField(PriorityField::Unknown(115))
+        );
+        assert_eq!(u8::from(PriorityField::Unknown(115)), 115);
+
+        // T-Mobile has been seen sending this in X-Mms-Adaptation-Allowed
+        let (_, v) = parse_header_field(XMmsAdaptationAllowed, &[115]).unwrap();
+        assert_eq!(v, MmsHeaderValue::YesNoField(YesNoField::Unknown(115)));
+
+        let (_, v) = parse_header_field(XMmsDeliveryReport, &[0x80]).unwrap();
+        assert_eq!(v, MmsHeaderValue::YesNoField(YesNoField::Yes));
+    }
+}
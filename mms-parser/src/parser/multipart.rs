@@ -1,43 +1,283 @@
 use super::*;
 use crate::{
+    error::{InputError, ParseError},
     pdu::take_field,
-    types::multipart::{MultiPartBody, MultiPartItem},
-    wap_header_item,
+    types::{
+        multipart::{is_multipart, MultiPartBody, MultiPartItem, PartBody},
+        MessageHeader,
+    },
 };
 
-use nom::{combinator::all_consuming, do_parse, multi::many0, named};
-
-named!(
-    body_item<MultiPartItem>,
-    do_parse!(
-        headers_len: uintvar
-            >> data_len: uintvar
-            >> content_type_bytes: take_field
-            >> headers: take!(headers_len - content_type_bytes.len() as u64)
-            >> body: take!(data_len)
-            >> (MultiPartItem {
-                content_type: all_consuming(parse_content_type)(
-                    content_type_bytes
-                )?
-                .1,
-                headers: all_consuming(many0(wap_header_item))(headers)?.1,
-                body: body.to_vec(),
-            })
-    )
-);
-
-// TODO: In WAP 1.3 the num_entries (nEntries in the spec) header becomes
-// optional, but recommended, so there could be either 2, or 3 uintvars at the
-// beginning of the body
-pub fn parse_multipart_body(data: &[u8]) -> IResult<&[u8], MultiPartBody> {
-    let (mut data, _num_entries) = uintvar(data)?;
-    let mut items = Vec::new();
-
-    while data.len() > 0 {
-        let (d, item) = body_item(data)?;
-        items.push(item);
-        data = d;
-    }
-
-    Ok((data, items))
+use log::debug;
+use nom::combinator::all_consuming;
+
+/// How many multipart bodies can be nested inside each other, parts nested
+/// deeper than this are left undecoded
+pub const MAX_MULTIPART_DEPTH: usize = 8;
+
+/// Parse the headers of a part, which start with it's content type
+pub(crate) fn part_headers(
+    d: &[u8],
+) -> IResult<&[u8], (mime::Mime, Vec<MessageHeader>)> {
+    let (d, content_type_bytes) = take_field(d)?;
+    let (_, content_type) =
+        all_consuming(parse_content_type)(content_type_bytes)?;
+    let (d, headers) = wap_headers(d)?;
+
+    Ok((d, (content_type, headers)))
+}
+
+/// A part which has been split out of a multipart body, with it's body not yet
+/// decoded
+type RawPart<'a> = (mime::Mime, Vec<MessageHeader>, &'a [u8]);
+
+fn raw_part(d: &[u8]) -> IResult<&[u8], RawPart<'_>> {
+    let (d, headers_len) = uintvar(d)?;
+    let (d, data_len) = uintvar(d)?;
+    let (d, headers) = take(headers_len)(d)?;
+    let (d, body) = take(data_len)(d)?;
+
+    let (_, (content_type, headers)) = part_headers(headers)?;
+
+    Ok((d, (content_type, headers, body)))
+}
+
+fn raw_parts(mut d: &[u8]) -> IResult<&[u8], Vec<RawPart<'_>>> {
+    let mut parts = Vec::new();
+
+    while !d.is_empty() {
+        let (r, part) = raw_part(d)?;
+        parts.push(part);
+        d = r;
+    }
+
+    Ok((d, parts))
+}
+
+/// Decode the bodies of parts, parsing nested multipart bodies
+fn body_items(
+    parts: Vec<RawPart<'_>>,
+    depth: usize,
+    strict: bool,
+) -> Result<Vec<MultiPartItem<'_>>, nom::Err<InputError<&[u8]>>> {
+    let mut items = Vec::with_capacity(parts.len());
+
+    for (content_type, headers, body) in parts {
+        let body = if !is_multipart(&content_type) {
+            PartBody::from(body)
+        } else if depth >= MAX_MULTIPART_DEPTH {
+            debug!("Not decoding multipart nested {} levels deep", depth + 1);
+            PartBody::from(body)
+        } else {
+            match multipart_body(body, depth + 1, strict) {
+                Ok((_, nested)) => PartBody::MultiPart(nested),
+                Err(e) if !strict => {
+                    debug!("Not decoding invalid nested multipart: {:?}", e);
+                    PartBody::from(body)
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        items.push(MultiPartItem {
+            content_type,
+            headers,
+            body,
+        });
+    }
+
+    Ok(items)
+}
+
+// In WAP 1.3 the num_entries (nEntries in the spec) header becomes optional,
+// but recommended, so there could be either 2, or 3 uintvars at the beginning
+// of the body. A body is assumed to have num_entries if it matches the number
+// of parts, otherwise if the parts can be split without it, it's assumed to
+// be missing. When not strict, a num_entries which doesn't match the number of
+// parts is also accepted.
+//
+// Only the layout of this level is used to decide, nested bodies are parsed
+// once afterwards, so nesting doesn't multiply the work.
+fn multipart_body(
+    data: &[u8],
+    depth: usize,
+    strict: bool,
+) -> IResult<&[u8], MultiPartBody<'_>> {
+    let with_num_entries = uintvar(data).and_then(|(d, num_entries)| {
+        raw_parts(d).map(|(d, parts)| (d, (num_entries, parts)))
+    });
+
+    let (d, has_num_entries, parts) = match with_num_entries {
+        Ok((d, (num_entries, parts))) if parts.len() as u64 == num_entries => {
+            (d, true, parts)
+        }
+        with_num_entries => match raw_parts(data) {
+            Ok((d, parts)) => (d, false, parts),
+            Err(_) => {
+                let (d, (num_entries, parts)) = with_num_entries?;
+                if strict {
+                    return invalid(
+                        data,
+                        format!(
+                            "Multipart body has {} entries, but {} were declared",
+                            parts.len(),
+                            num_entries
+                        ),
+                    );
+                }
+
+                debug!(
+                    "Multipart body has {} entries, but {} were declared",
+                    parts.len(),
+                    num_entries
+                );
+                (d, true, parts)
+            }
+        },
+    };
+
+    let parts = body_items(parts, depth, strict)?;
+    let body = if has_num_entries {
+        MultiPartBody::new(parts)
+    } else {
+        MultiPartBody::without_num_entries(parts)
+    };
+
+    Ok((d, body))
+}
+
+/// Parse a multipart body, accepting bodies where the number of entries
+/// doesn't match the number of parts
+pub fn parse_multipart_body(
+    data: &[u8],
+) -> Result<MultiPartBody<'_>, ParseError> {
+    multipart_body(data, 0, false)
+        .map(|(_, body)| body)
+        .map_err(|e| ParseError::from_nom(data, e))
+}
+
+/// Parse a multipart body, failing if the number of entries doesn't match the
+/// number of parts
+pub fn parse_multipart_body_strict(
+    data: &[u8],
+) -> Result<MultiPartBody<'_>, ParseError> {
+    multipart_body(data, 0, true)
+        .map(|(_, body)| body)
+        .map_err(|e| ParseError::from_nom(data, e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn part(content_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut part = vec![1, body.len() as u8, content_type];
+        part.extend_from_slice(body);
+        part
+    }
+
+    #[test]
+    fn nested_multipart() {
+        let mut inner = vec![2];
+        inner.append(&mut part(0x83, b"text"));
+        inner.append(&mut part(0x9E, b"jpeg"));
+
+        let mut outer = vec![2];
+        outer.append(&mut part(0x83, b"first"));
+        outer.append(&mut part(0xA6, &inner));
+
+        let body = parse_multipart_body(&outer).unwrap();
+
+        assert_eq!(body.parts.len(), 2);
+        match &body.parts[1].body {
+            PartBody::MultiPart(b) => assert_eq!(b.parts.len(), 2),
+            b => panic!("Unexpected body {:?}", b),
+        }
+
+        let leaves: Vec<_> = body
+            .leaves()
+            .iter()
+            .map(|p| p.body.data().unwrap())
+            .collect();
+        assert_eq!(leaves, vec![&b"first"[..], b"text", b"jpeg"]);
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let mut body = vec![1];
+        body.append(&mut part(0x83, b"text"));
+
+        for _ in 0..=MAX_MULTIPART_DEPTH {
+            let mut outer = vec![1];
+            outer.append(&mut part(0xA6, &body));
+            body = outer;
+        }
+
+        let mut body = &parse_multipart_body(&body).unwrap();
+        for _ in 0..MAX_MULTIPART_DEPTH {
+            body = match &body.parts[0].body {
+                PartBody::MultiPart(b) => b,
+                b => panic!("Unexpected body {:?}", b),
+            };
+        }
+
+        assert!(body.parts[0].body.data().is_some());
+    }
+
+    #[test]
+    fn invalid_nested_multipart_is_kept() {
+        let mut outer = vec![2];
+        outer.append(&mut part(0x83, b"first"));
+        outer.append(&mut part(0xA6, &[1, 5, 1, 0x83]));
+
+        parse_multipart_body_strict(&outer).unwrap_err();
+
+        let body = parse_multipart_body(&outer).unwrap();
+        assert_eq!(body.parts.len(), 2);
+        assert_eq!(body.parts[0].body.data(), Some(&b"first"[..]));
+        assert_eq!(body.parts[1].body.data(), Some(&[1, 5, 1, 0x83][..]));
+    }
+
+    #[test]
+    fn without_num_entries() {
+        let mut data = part(0x83, b"first");
+        data.append(&mut part(0x83, b"second"));
+
+        let body = parse_multipart_body_strict(&data).unwrap();
+
+        assert!(!body.has_num_entries);
+        assert_eq!(body.parts.len(), 2);
+    }
+
+    #[test]
+    fn wrong_num_entries() {
+        let mut data = vec![3];
+        data.append(&mut part(0x83, b"first"));
+        data.append(&mut part(0x83, b"second"));
+
+        parse_multipart_body_strict(&data).unwrap_err();
+
+        let body = parse_multipart_body(&data).unwrap();
+        assert!(body.has_num_entries);
+        assert_eq!(body.parts.len(), 2);
+    }
+
+    #[test]
+    fn parts_borrow_from_data() {
+        use std::borrow::Cow;
+
+        let mut data = vec![1];
+        data.append(&mut part(0x83, b"text"));
+
+        let body = parse_multipart_body(&data).unwrap();
+        assert!(matches!(
+            body.parts[0].body,
+            PartBody::Data(Cow::Borrowed(_))
+        ));
+        assert!(matches!(body.parts[0].text(), Some(Cow::Borrowed("text"))));
+
+        let body = body.into_owned();
+        drop(data);
+        assert_eq!(body.parts[0].body.data(), Some(&b"text"[..]));
+    }
 }
@@ -1,17 +1,23 @@
+use crate::error::{invalid, IResult};
 use nom::bytes::complete::take;
-use nom::IResult;
 
 // TODO: a usize should work in most cases, but there's no reason a uintvar
 // can't be bigger than a usize. It would be better to use something like
 // num-bigint
 /// A uintvar is a composed of 8 bit sequences, the first bit is 1 when ther are
 /// following sequences, and 0 when it is the last byte
-pub fn uintvar(d: &[u8]) -> IResult<&[u8], u64> {
+pub fn uintvar(input: &[u8]) -> IResult<&[u8], u64> {
     let mut nums: Vec<u8> = Vec::new();
-    let mut d = d;
+    let mut d = input;
     let mut carry = true;
 
     while carry {
+        // The spec limits uintvars to 5 bytes, anything longer than what fits
+        // in a u64 can't be handled here
+        if nums.len() >= 9 {
+            return invalid(input, "Uintvar is too large to fit in a u64");
+        }
+
         let (nd, (c, value)) = take_uintvar_byte(d)?;
         carry = c;
 
@@ -1,28 +1,36 @@
 // TODO: This file has a tarible name, all of its contents should probably be
 // moved to lib.rs or the parser module
 use crate::{
+    error::{invalid, IResult, ParseError},
     parser::*,
-    types::mms_header::{MmsHeader, MmsHeaderValue},
+    types::mms_header::{MessageTypeField, MmsHeader, MmsHeaderValue},
 };
 
 use crate::MultiMap;
-use nom::{bytes::complete::take, IResult};
+use log::debug;
+use nom::bytes::complete::take;
 
 fn parse_header_name(d: &[u8]) -> IResult<&[u8], MmsHeader> {
-    let (d, header_byte) = take(1u8)(d)?;
+    let (r, header_byte) = take(1u8)(d)?;
     if header_byte[0] & 0x80 == 0 {
-        // TODO: No need to panic, just return an error
-        panic!("{:#04X} doesn't have it's 8th bit set to 1", header_byte[0])
+        return invalid(
+            d,
+            format!(
+                "{:#04X} doesn't have it's 8th bit set to 1",
+                header_byte[0]
+            ),
+        );
     }
     let header_byte = header_byte[0] & 0x7F;
 
-    Ok((d, MmsHeader::from(header_byte)))
+    Ok((r, MmsHeader::from(header_byte)))
 }
 
-pub(crate) fn take_header_field(d: &[u8]) -> IResult<&[u8], (MmsHeader, Vec<u8>)> {
-    let (d, header_byte) = parse_header_name(d)?;
-    let (d, header_value) = take_field(d)?;
-    Ok((d, (header_byte, header_value.to_vec())))
+fn take_header_field(d: &[u8]) -> IResult<&[u8], (MmsHeader, &[u8])> {
+    let (d, header) = parse_header_name(d)?;
+    let (d, header_value) =
+        take_field(d).map_err(|e| e.map(|e| e.in_header(&header)))?;
+    Ok((d, (header, header_value)))
 }
 
 pub(crate) fn take_field(d: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -44,7 +52,9 @@ pub(crate) fn take_field(d: &[u8]) -> IResult<&[u8], &[u8]> {
     Ok((d, header_value))
 }
 
-pub fn split_header_fields(d: &[u8]) -> IResult<&[u8], Vec<(MmsHeader, Vec<u8>)>> {
+fn split_header_fields_ref(
+    d: &[u8],
+) -> IResult<&[u8], Vec<(MmsHeader, &[u8])>> {
     let mut header_fields = Vec::new();
     let mut data = d;
 
@@ -56,71 +66,206 @@ pub fn split_header_fields(d: &[u8]) -> IResult<&[u8], Vec<(MmsHeader, Vec<u8>)>
         header_fields.push(header);
 
         // Header side effects
-        match header_name {
-            MmsHeader::ContentType => {
-                // I don't know of any cases where the content type field would
-                // not be the last one, but I don't know that there aren't
-                header_fields.push((MmsHeader::ImplicitBody, d.to_vec()));
-                data = &[]
-            }
-            _ => {}
+        if header_name == MmsHeader::ContentType {
+            // I don't know of any cases where the content type field would
+            // not be the last one, but I don't know that there aren't
+            header_fields.push((MmsHeader::ImplicitBody, d));
+            data = &d[d.len()..];
         }
     }
 
     Ok((data, header_fields))
 }
 
+pub fn split_header_fields(
+    d: &[u8],
+) -> Result<Vec<(MmsHeader, Vec<u8>)>, ParseError> {
+    let (_, fields) =
+        split_header_fields_ref(d).map_err(|e| ParseError::from_nom(d, e))?;
+
+    Ok(fields
+        .into_iter()
+        .map(|(header, value)| (header, value.to_vec()))
+        .collect())
+}
+
 pub fn parse_header_fields(
-    fields: &Vec<(MmsHeader, Vec<u8>)>,
+    fields: &[(MmsHeader, Vec<u8>)],
 ) -> MultiMap<MmsHeader, MmsHeaderValue> {
     parse_header_fields_with_errors(fields)
         .iter()
         .filter_map(|h| match h.1 {
             Ok(d) => Some((h.0.clone(), d.clone())),
-            Err(_) => None,
+            Err(e) => {
+                debug!("Dropping header which couldn't be parsed: {}", e);
+                None
+            }
         })
         .collect()
 }
 
-// TODO: Return a parse error of some sort that can print a user readable error
-// see https://github.com/Geal/nom/blob/master/examples/json.rs for an example
 pub fn parse_mms_pdu(
     d: &[u8],
-) -> IResult<&[u8], crate::types::VndWapMmsMessage> {
-    let (d, split) = split_header_fields(d)?;
+) -> Result<crate::types::VndWapMmsMessage<'_>, ParseError> {
+    let (_, split) =
+        split_header_fields_ref(d).map_err(|e| ParseError::from_nom(d, e))?;
+
+    // The body is borrowed rather than parsed like the other headers
+    let body = split
+        .iter()
+        .find(|(header, _)| *header == MmsHeader::ImplicitBody)
+        .map(|(_, body)| *body)
+        .unwrap_or_default();
+    let raw_headers: Vec<(MmsHeader, &[u8])> = split
+        .into_iter()
+        .filter(|(header, _)| *header != MmsHeader::ImplicitBody)
+        .collect();
+
+    let message_type = find_message_type(&raw_headers);
+    let headers: MultiMap<MmsHeader, MmsHeaderValue> = raw_headers
+        .iter()
+        .filter_map(|(header, value)| {
+            match parse_header_value(message_type.as_ref(), header, value) {
+                Ok(v) => Some((header.clone(), v)),
+                Err(e) => {
+                    let e = ParseError::from_nom(d, e);
+                    debug!("Dropping header which couldn't be parsed: {}", e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    Ok(crate::types::VndWapMmsMessage {
+        headers,
+        body: body.into(),
+        raw_headers: raw_headers
+            .into_iter()
+            .map(|(header, value)| (header, value.into()))
+            .collect(),
+    })
+}
+
+/// Find the X-Mms-Message-Type in unparsed headers, it's needed to parse the
+/// headers which are encoded differently depending on the message type
+pub(crate) fn find_message_type<V: AsRef<[u8]>>(
+    fields: &[(MmsHeader, V)],
+) -> Option<MessageTypeField> {
+    let (_, value) = fields
+        .iter()
+        .find(|(header, _)| *header == MmsHeader::XMmsMessageType)?;
 
-    let mut headers = parse_header_fields(&split);
-    let body = match headers
-        .remove(&MmsHeader::ImplicitBody)
-        .unwrap_or(vec![].into())
+    match parse_header_value(None, &MmsHeader::XMmsMessageType, value.as_ref())
     {
-        MmsHeaderValue::Bytes(bytes) => bytes,
-        _ => vec![],
+        Ok(MmsHeaderValue::MessageTypeField(t)) => Some(t),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_header_value<'a>(
+    message_type: Option<&MessageTypeField>,
+    header: &MmsHeader,
+    value: &'a [u8],
+) -> Result<MmsHeaderValue, nom::Err<crate::error::InputError<&'a [u8]>>> {
+    use crate::parser::mms_header::{
+        parse_delete_conf_field, parse_header_field,
     };
 
-    Ok((d, crate::types::VndWapMmsMessage { headers, body }))
+    let parsed = match message_type {
+        Some(MessageTypeField::MDeleteConf)
+        | Some(MessageTypeField::MMboxDeleteConf) => {
+            parse_delete_conf_field(header.clone(), value)
+        }
+        _ => parse_header_field(header.clone(), value),
+    };
+    parsed
+        .map(|(_, v)| v)
+        .map_err(|e| e.map(|e| e.in_header(header)))
 }
 
-pub fn parse_header_fields_with_errors<'a>(
-    fields: &'a Vec<(MmsHeader, Vec<u8>)>,
-) -> MultiMap<
-    MmsHeader,
-    Result<MmsHeaderValue, nom::Err<nom::error::Error<&'a [u8]>>>,
-> {
+/// Parse each header, keeping the errors for headers which couldn't be parsed.
+///
+/// Error offsets are counted from the start of the first field, assuming
+/// `fields` was produced by [`split_header_fields`].
+pub fn parse_header_fields_with_errors(
+    fields: &[(MmsHeader, Vec<u8>)],
+) -> MultiMap<MmsHeader, Result<MmsHeaderValue, ParseError>> {
+    let message_type = find_message_type(fields);
+    let mut offset = 0;
+
     fields
         .iter()
-        .map(|i| {
-            let value = match crate::parser::mms_header::parse_header_field(
-                i.0.clone(),
-                &*i.1,
-            ) {
-                Ok((_r, v)) => v,
-                Err(e) => {
-                    let err = e;
-                    return (i.0.clone(), Err(err));
-                }
-            };
-            (i.0.clone(), Ok(value))
+        .map(|(header, value)| {
+            // Every field except the body is preceded by it's header byte
+            if *header != MmsHeader::ImplicitBody {
+                offset += 1;
+            }
+
+            let parsed =
+                parse_header_value(message_type.as_ref(), header, value)
+                    .map_err(|e| ParseError::from_nom(value, e).shift(offset));
+
+            offset += value.len();
+            (header.clone(), parsed)
         })
         .collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::mms_header::MessageTypeField;
+
+    #[test]
+    fn header_name_without_high_bit() {
+        let err = parse_mms_pdu(&[0x8C, 0x80, 0x18]).unwrap_err();
+
+        assert_eq!(err.offset(), Some(2));
+    }
+
+    #[test]
+    fn truncated_header_value() {
+        let err = parse_mms_pdu(&[0x8C, 0x80, 0x89, 0x05, 0x80]).unwrap_err();
+
+        assert_eq!(
+            err,
+            ParseError::Incomplete {
+                offset: 3,
+                header: Some(MmsHeader::From),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_message_type_is_kept() {
+        let message = parse_mms_pdu(&[0x8C, 0xFF, 0x98, b'a', 0]).unwrap();
+
+        assert_eq!(
+            message.x_mms_message_type(),
+            Some(&MessageTypeField::Unknown(0xFF))
+        );
+        assert_eq!(message.x_mms_transaction_id().unwrap(), "a");
+    }
+
+    #[test]
+    fn error_offsets_are_absolute() {
+        let fields =
+            split_header_fields(&[0x98, b'a', 0, 0x89, 0x01, 0x85]).unwrap();
+        let parsed = parse_header_fields_with_errors(&fields);
+
+        let err = parsed.get(&MmsHeader::From).unwrap().clone().unwrap_err();
+        assert_eq!(err.offset(), Some(5));
+        assert_eq!(err.header(), Some(&MmsHeader::From));
+    }
+
+    #[test]
+    fn body_is_borrowed() {
+        let data = vec![0x8C, 0x80, 0x84, 0x83, b'h', b'i'];
+        let message = parse_mms_pdu(&data).unwrap();
+        assert!(matches!(message.body, std::borrow::Cow::Borrowed(b"hi")));
+
+        let message = message.into_owned();
+        drop(data);
+        assert_eq!(&*message.body, b"hi");
+    }
+}
@@ -0,0 +1,447 @@
+//! Decode messages from a reader without holding the whole message in memory
+
+use crate::{
+    parse_mms_pdu,
+    parser::{part_headers, uintvar},
+    types::{MessageHeader, MmsHeader, VndWapMmsMessage},
+    ParseError,
+};
+
+use log::debug;
+use std::io::{self, Read};
+
+/// The largest headers of a part which are accepted, so a bad length can't
+/// make the whole body be buffered
+const MAX_PART_HEADERS_LEN: u64 = 64 * 1024;
+
+fn invalid_data(e: ParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn truncated(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, msg)
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        return match reader.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+    }
+}
+
+/// Read the bytes of a uintvar into `buf`, returning it's value
+fn read_uintvar<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<u64> {
+    let start = buf.len();
+    loop {
+        let byte = read_byte(reader)?
+            .ok_or_else(|| truncated("Uintvar is truncated"))?;
+        buf.push(byte);
+
+        if byte & 0x80 == 0 || buf.len() - start > 9 {
+            break;
+        }
+    }
+
+    uintvar(&buf[start..])
+        .map(|(_, v)| v)
+        .map_err(|e| invalid_data(ParseError::from_nom(&buf[start..], e)))
+}
+
+/// Read exactly `len` bytes into `buf`
+fn read_len<R: Read>(
+    reader: &mut R,
+    len: u64,
+    buf: &mut Vec<u8>,
+) -> io::Result<()> {
+    let read = reader.take(len).read_to_end(buf)?;
+    if (read as u64) < len {
+        return Err(truncated("Value is truncated"));
+    }
+    Ok(())
+}
+
+/// Read the bytes of one header value into `buf`, the length of the value is
+/// found the same way as [`take_field`](crate::pdu::take_field)
+fn read_field<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<()> {
+    let first_byte = read_byte(reader)?
+        .ok_or_else(|| truncated("Header value is missing"))?;
+    buf.push(first_byte);
+
+    match first_byte {
+        0..=30 => read_len(reader, first_byte as u64, buf),
+        31 => {
+            let len = read_uintvar(reader, buf)?;
+            read_len(reader, len, buf)
+        }
+        32..=127 => loop {
+            match read_byte(reader)? {
+                Some(0) => {
+                    buf.push(0);
+                    return Ok(());
+                }
+                Some(b) => buf.push(b),
+                None => return Err(truncated("Text-string is truncated")),
+            }
+        },
+        128..=255 => Ok(()),
+    }
+}
+
+/// Read the headers of a mms pdu, stopping after the Content-Type so the
+/// reader is left at the start of the body.
+///
+/// The body of the returned message is always empty. If the body is a
+/// multipart body it can be read with [`MultiPartReader`]. A reader which
+/// ends in the middle of a header fails with
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof).
+pub fn read_mms_pdu_headers<R: Read>(
+    reader: &mut R,
+) -> io::Result<VndWapMmsMessage<'static>> {
+    let mut buf = Vec::new();
+
+    while let Some(header) = read_byte(reader)? {
+        buf.push(header);
+        read_field(reader, &mut buf)?;
+
+        if MmsHeader::from(header & 0x7F) == MmsHeader::ContentType {
+            break;
+        }
+    }
+
+    parse_mms_pdu(&buf)
+        .map(|message| message.into_owned())
+        .map_err(invalid_data)
+}
+
+/// Reads the parts of a multipart body one at a time.
+///
+/// Each part is returned as a [`PartReader`], which only reads that part's
+/// data, so it can be copied somewhere else without holding it in memory.
+/// Parts which are multipart bodies themselves can be read by creating
+/// another `MultiPartReader` from them.
+pub struct MultiPartReader<R> {
+    reader: R,
+    /// Bytes which were read ahead while finding the first part
+    buffered: Vec<u8>,
+    started: bool,
+    /// How many parts are left, if the body says how many parts it has
+    remaining_parts: Option<u64>,
+    /// How much of the data of the current part hasn't been read yet
+    remaining_data: u64,
+}
+
+impl<R: Read> MultiPartReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffered: Vec::new(),
+            started: false,
+            remaining_parts: None,
+            remaining_data: 0,
+        }
+    }
+
+    /// Read the headers of the next part, skipping any of the previous part's
+    /// data which wasn't read. Returns `None` once there are no more parts.
+    ///
+    /// A body which ends before all of it's declared parts fails with
+    /// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof).
+    pub fn next_part(&mut self) -> io::Result<Option<PartReader<'_, R>>> {
+        if !self.started {
+            self.find_first_part()?;
+            self.started = true;
+        }
+
+        self.skip_data()?;
+
+        let at_end = self.fill(1)?.is_empty();
+        match self.remaining_parts {
+            Some(0) => return Ok(None),
+            None if at_end => return Ok(None),
+            Some(_) if at_end => {
+                return Err(truncated(
+                    "Multipart body has fewer parts than declared",
+                ))
+            }
+            _ => {}
+        }
+
+        let mut buf = Vec::new();
+        let headers_len = read_uintvar(self, &mut buf)?;
+        let data_len = read_uintvar(self, &mut buf)?;
+        if headers_len > MAX_PART_HEADERS_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The headers of a part are too long",
+            ));
+        }
+
+        let mut headers = Vec::new();
+        read_len(self, headers_len, &mut headers)?;
+        let (_, (content_type, headers)) = part_headers(&headers)
+            .map_err(|e| invalid_data(ParseError::from_nom(&headers, e)))?;
+
+        self.remaining_parts = self.remaining_parts.map(|n| n - 1);
+        self.remaining_data = data_len;
+
+        Ok(Some(PartReader {
+            content_type,
+            headers,
+            data_len,
+            reader: self,
+        }))
+    }
+
+    /// Make sure at least `len` bytes are buffered, unless the reader ends
+    /// first, and return the buffered bytes
+    fn fill(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.buffered.len() < len {
+            let missing = len - self.buffered.len();
+            (&mut self.reader)
+                .take(missing as u64)
+                .read_to_end(&mut self.buffered)?;
+        }
+        Ok(&self.buffered)
+    }
+
+    // The number of entries is optional since WAP 1.3, see
+    // `parser::multipart::multipart_body`. Without reading the whole body the
+    // only way to tell whether it's there, is to check which of the two
+    // possible positions the headers of the first part are at. The form with
+    // the number of entries is preferred when both are possible.
+    fn find_first_part(&mut self) -> io::Result<()> {
+        let peek = self.fill(15)?.to_vec();
+        if peek.is_empty() {
+            return Ok(());
+        }
+
+        let (r, first) = uintvar(&peek)
+            .map_err(|e| invalid_data(ParseError::from_nom(&peek, e)))?;
+        let after_first = peek.len() - r.len();
+        if first == 0 && r.is_empty() {
+            // An empty body with the number of entries
+            self.buffered.drain(..after_first);
+            self.remaining_parts = Some(0);
+            return Ok(());
+        }
+
+        let second = uintvar(r).ok();
+        let third = second.and_then(|(r, _)| uintvar(r).ok());
+
+        if let (Some((_, headers_len)), Some((r, _))) = (second, third) {
+            let start = peek.len() - r.len();
+            if self.starts_with_part_headers(start, headers_len)? {
+                self.buffered.drain(..after_first);
+                self.remaining_parts = Some(first);
+                return Ok(());
+            }
+        }
+
+        if let Some((r, _)) = second {
+            let start = peek.len() - r.len();
+            if self.starts_with_part_headers(start, first)? {
+                debug!("Multipart body doesn't have a number of entries");
+                return Ok(());
+            }
+        }
+
+        Err(invalid_data(ParseError::Invalid {
+            offset: 0,
+            header: None,
+            reason: "Couldn't find the first part of the multipart body"
+                .to_string(),
+        }))
+    }
+
+    /// Whether the headers of a part, which are `len` bytes long, are at
+    /// `start` in the buffered bytes
+    fn starts_with_part_headers(
+        &mut self,
+        start: usize,
+        len: u64,
+    ) -> io::Result<bool> {
+        if len > MAX_PART_HEADERS_LEN {
+            return Ok(false);
+        }
+
+        let len = len as usize;
+        let d = self.fill(start + len)?;
+        if d.len() < start + len {
+            return Ok(false);
+        }
+
+        Ok(
+            matches!(part_headers(&d[start..start + len]), Ok((r, _)) if r.is_empty()),
+        )
+    }
+
+    fn skip_data(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        while self.remaining_data > 0 {
+            let len = (buf.len() as u64).min(self.remaining_data) as usize;
+            let read = self.read_data(&mut buf[..len])?;
+            self.remaining_data -= read as u64;
+        }
+        Ok(())
+    }
+
+    fn read_data(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.read(buf)? {
+            0 => Err(truncated("Part is truncated")),
+            read => Ok(read),
+        }
+    }
+}
+
+impl<R: Read> Read for MultiPartReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffered.is_empty() {
+            return self.reader.read(buf);
+        }
+
+        let len = buf.len().min(self.buffered.len());
+        buf[..len].copy_from_slice(&self.buffered[..len]);
+        self.buffered.drain(..len);
+        Ok(len)
+    }
+}
+
+/// A part of a multipart body, reading from it reads the part's data.
+///
+/// Reading fails with [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) if
+/// the body ends before all of the part's data.
+pub struct PartReader<'a, R: Read> {
+    pub content_type: mime::Mime,
+    pub headers: Vec<MessageHeader>,
+    /// The length of the part's data
+    pub data_len: u64,
+    reader: &'a mut MultiPartReader<R>,
+}
+
+impl<R: Read> Read for PartReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.reader.remaining_data;
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let len = (buf.len() as u64).min(remaining) as usize;
+        let read = self.reader.read_data(&mut buf[..len])?;
+        self.reader.remaining_data -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn part(content_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut part = vec![1, body.len() as u8, content_type];
+        part.extend_from_slice(body);
+        part
+    }
+
+    fn read_parts<R: Read>(mut reader: MultiPartReader<R>) -> Vec<Vec<u8>> {
+        let mut parts = Vec::new();
+        while let Some(mut part) = reader.next_part().unwrap() {
+            let mut data = Vec::new();
+            part.read_to_end(&mut data).unwrap();
+            parts.push(data);
+        }
+        parts
+    }
+
+    #[test]
+    fn headers_then_parts() {
+        let mut data = vec![0x8C, 0x84, 0x98, b'a', 0, 0x84, 0xA3, 2];
+        data.append(&mut part(0x83, b"text"));
+        data.append(&mut part(0x9E, b"jpeg"));
+
+        let mut reader = &data[..];
+        let message = read_mms_pdu_headers(&mut reader).unwrap();
+        assert_eq!(message.x_mms_transaction_id().unwrap(), "a");
+        assert!(message.has_multipart_body());
+        assert!(message.body.is_empty());
+
+        let parts = read_parts(MultiPartReader::new(reader));
+        assert_eq!(parts, [&b"text"[..], &b"jpeg"[..]]);
+    }
+
+    #[test]
+    fn without_num_entries() {
+        let mut data = part(0x83, b"text");
+        data.append(&mut part(0x9E, b"jpeg"));
+
+        let parts = read_parts(MultiPartReader::new(&data[..]));
+        assert_eq!(parts, [&b"text"[..], &b"jpeg"[..]]);
+    }
+
+    #[test]
+    fn unread_data_is_skipped() {
+        let mut data = vec![2];
+        data.append(&mut part(0x83, b"text"));
+        data.append(&mut part(0x9E, b"jpeg"));
+
+        let mut reader = MultiPartReader::new(&data[..]);
+        reader.next_part().unwrap().unwrap();
+        let part = reader.next_part().unwrap().unwrap();
+        assert_eq!(part.content_type, mime::IMAGE_JPEG);
+        assert_eq!(part.data_len, 4);
+    }
+
+    #[test]
+    fn nested_parts() {
+        let mut inner = vec![1];
+        inner.append(&mut part(0x83, b"text"));
+        let mut data = vec![1];
+        data.append(&mut part(0xA3, &inner));
+
+        let mut reader = MultiPartReader::new(&data[..]);
+        let mut outer = reader.next_part().unwrap().unwrap();
+        let parts = read_parts(MultiPartReader::new(&mut outer));
+        assert_eq!(parts, [&b"text"[..]]);
+    }
+
+    #[test]
+    fn truncated_part() {
+        let mut data = vec![1];
+        data.append(&mut part(0x83, b"text"));
+        data.truncate(data.len() - 1);
+
+        let mut reader = MultiPartReader::new(&data[..]);
+        let mut part = reader.next_part().unwrap().unwrap();
+        let err = part.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn long_part_headers_are_rejected() {
+        let mut data = vec![2];
+        data.append(&mut part(0x83, b"text"));
+        // A part with 1MiB of headers
+        data.extend_from_slice(&[0xC0, 0x80, 0x00, 0x00]);
+        data.resize(data.len() + 1024 * 1024, 0x83);
+
+        let mut reader = MultiPartReader::new(&data[..]);
+        reader.next_part().unwrap().unwrap();
+        let err = reader.next_part().map(|_| ()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn missing_parts() {
+        let mut data = vec![2];
+        data.append(&mut part(0x83, b"text"));
+
+        let mut reader = MultiPartReader::new(&data[..]);
+        reader.next_part().unwrap().unwrap();
+        let err = reader.next_part().map(|_| ()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,13 +1,88 @@
+/// Generates an enum for a value which is a single token, from a table of
+/// `Variant = value => "name"` entries, where the name is the one used in the
+/// spec. Tokens which aren't listed are kept in `Unknown` so they can still be
+/// encoded, and are displayed as their number.
+macro_rules! token_enum {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:literal => $display:literal
+            ),+
+            $(,)*
+        }
+    )+) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum $name {
+                $($(#[$variant_meta])* $variant,)+
+                Unknown(u8),
+            }
+
+            impl std::convert::From<u8> for $name {
+                fn from(i: u8) -> Self {
+                    match i {
+                        $($value => Self::$variant,)+
+                        i => Self::Unknown(i),
+                    }
+                }
+            }
+
+            impl std::convert::From<$name> for u8 {
+                fn from(v: $name) -> u8 {
+                    match v {
+                        $($name::$variant => $value,)+
+                        $name::Unknown(i) => i,
+                    }
+                }
+            }
+
+            impl std::fmt::Display for $name {
+                fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    match self {
+                        $(Self::$variant => write!(f, $display),)+
+                        Self::Unknown(i) => write!(f, "{}", i),
+                    }
+                }
+            }
+
+            impl std::str::FromStr for $name {
+                type Err = &'static str;
+
+                /// Parse the name of a token, ignoring case, or it's number
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    $(
+                        if s.eq_ignore_ascii_case($display) {
+                            return Ok(Self::$variant);
+                        }
+                    )+
+                    s.parse::<u8>()
+                        .map(Self::from)
+                        .map_err(|_| concat!("Unknown ", stringify!($name)))
+                }
+            }
+        )+
+    };
+}
+
+pub mod address;
+pub mod charset_codes;
 pub mod content_type_codes;
 pub mod message_header;
 pub mod mms_header;
 pub mod multipart;
+pub mod parameter_codes;
+pub mod pdu;
 
+pub use address::Address;
 pub use message_header::*;
 pub use mms_header::{MmsHeader, MmsHeaderValue};
-use PduType::*;
+pub use pdu::Pdu;
 
 use crate::MultiMap;
+use std::borrow::Cow;
 
 // use enum_primitive_derive::Primitive;
 // use num_enum::IntoPrimitive;
@@ -27,41 +102,25 @@ pub struct FetchResponse {
 // make this more generic
 #[derive(Derivative)]
 #[derivative(Debug)]
-pub struct Wap {
+pub struct Wap<'a> {
     pub transaction_id: u8,
     pub message_type: PduType,
     pub content_type: mime::Mime,
     pub headers: Vec<MessageHeader>,
     #[derivative(Debug = "ignore")]
-    pub data: Vec<u8>,
+    pub data: Cow<'a, [u8]>,
 }
 
-// TODO: Move the numbers in this match to the enum definition
-// and write From<u8>, and possibly Into<u8> with a macro
-impl From<u8> for PduType {
-    fn from(t: u8) -> Self {
-        match t {
-            1 => Connect,
-            2 => ConnectReply,
-            3 => Redirect,
-            4 => Reply,
-            5 => Disconnect,
-            6 => Push,
-            7 => ConfirmedPush,
-            8 => Suspend,
-            9 => Resume,
-            // unassigned block
-            64 => Get,
-            65 => Options,
-            66 => Head,
-            67 => Delete,
-            68 => Trace,
-            // unassigned block
-            96 => Post,
-            97 => Put,
-            // unassigned block
-            128 => DataFragment,
-            _ => Unknown(t),
+impl Wap<'_> {
+    /// Copy the data, so the message no longer borrows from the data it was
+    /// parsed from
+    pub fn into_owned(self) -> Wap<'static> {
+        Wap {
+            transaction_id: self.transaction_id,
+            message_type: self.message_type,
+            content_type: self.content_type,
+            headers: self.headers,
+            data: Cow::Owned(self.data.into_owned()),
         }
     }
 }
@@ -69,56 +128,77 @@ impl From<u8> for PduType {
 #[derive(Debug)]
 // TODO: This needs a better name
 // TODO: use getter methods instead of pub values?
-pub struct VndWapMmsMessage {
+pub struct VndWapMmsMessage<'a> {
     pub headers: MultiMap<MmsHeader, MmsHeaderValue>,
-    pub body: Vec<u8>,
+    pub body: Cow<'a, [u8]>,
+    /// Every header as it was encoded, in order, including headers which
+    /// couldn't be parsed. Used by
+    /// [`encode_mms_message_lossless`](crate::encoder::encode_mms_message_lossless)
+    /// to reproduce the original message, this is empty for messages which
+    /// weren't parsed
+    pub raw_headers: Vec<(MmsHeader, Cow<'a, [u8]>)>,
 }
 
-impl VndWapMmsMessage {
+impl VndWapMmsMessage<'_> {
     pub fn new(headers: MultiMap<MmsHeader, MmsHeaderValue>) -> Self {
         Self {
             headers,
-            body: Vec::new(),
+            body: Cow::Owned(Vec::new()),
+            raw_headers: Vec::new(),
         }
     }
 
     pub fn empty() -> Self {
         Self {
             headers: MultiMap::new(),
-            body: Vec::new(),
+            body: Cow::Owned(Vec::new()),
+            raw_headers: Vec::new(),
         }
     }
 
     pub fn has_multipart_body(&self) -> bool {
-        let ct = match self.content_type() {
-            Some(c) => c,
-            None => return false,
-        };
+        match self.content_type() {
+            Some(c) => multipart::is_multipart(c),
+            None => false,
+        }
+    }
 
-        ct.essence_str()
-            .starts_with("application/vnd.wap.multipart")
+    /// Copy the body and raw headers, so the message no longer borrows from
+    /// the data it was parsed from
+    pub fn into_owned(self) -> VndWapMmsMessage<'static> {
+        VndWapMmsMessage {
+            headers: self.headers,
+            body: Cow::Owned(self.body.into_owned()),
+            raw_headers: self
+                .raw_headers
+                .into_iter()
+                .map(|(h, v)| (h, Cow::Owned(v.into_owned())))
+                .collect(),
+        }
     }
 }
 
-#[derive(Debug)]
-// TODO: More descriptive name, pdu stands for protocal data unit
-pub enum PduType {
-    Connect,
-    ConnectReply,
-    Redirect,
-    Reply,
-    Disconnect,
-    Push,
-    ConfirmedPush,
-    Suspend,
-    Resume,
-    Get,
-    Options,
-    Head,
-    Delete,
-    Trace,
-    Post,
-    Put,
-    DataFragment,
-    Unknown(u8),
+// From WAP-230-WSP table 34
+token_enum! {
+    // TODO: More descriptive name, pdu stands for protocal data unit
+    /// Type of a WSP pdu
+    PduType {
+        Connect = 1 => "Connect",
+        ConnectReply = 2 => "ConnectReply",
+        Redirect = 3 => "Redirect",
+        Reply = 4 => "Reply",
+        Disconnect = 5 => "Disconnect",
+        Push = 6 => "Push",
+        ConfirmedPush = 7 => "ConfirmedPush",
+        Suspend = 8 => "Suspend",
+        Resume = 9 => "Resume",
+        Get = 64 => "Get",
+        Options = 65 => "Options",
+        Head = 66 => "Head",
+        Delete = 67 => "Delete",
+        Trace = 68 => "Trace",
+        Post = 96 => "Post",
+        Put = 97 => "Put",
+        DataFragment = 128 => "Data Fragment PDU",
+    }
 }
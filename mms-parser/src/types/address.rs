@@ -0,0 +1,159 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// An address in To, Cc, Bcc, or From, see OMA-TS-MMS_ENC-V1_3 section 8
+///
+/// Every address except email addresses is written with a `/TYPE=` suffix
+/// saying what kind of address it is, for example `+15551234/TYPE=PLMN`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// A phone number, either in the international E.164 form starting with
+    /// a `+`, or a national number or short code
+    Plmn(String),
+    /// An RFC 5322 email address
+    Email(String),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    /// An address with a type other than the ones above
+    UserDefined {
+        value: String,
+        address_type: String,
+    },
+    /// An address received from the network which isn't valid, such as an
+    /// alphanumeric sender id marked as a phone number, kept as it was
+    /// received
+    Unknown(String),
+}
+
+impl Address {
+    /// Parse an address received from the network, addresses which can't be
+    /// parsed are kept as [`Unknown`](Self::Unknown) rather than rejected,
+    /// use [`FromStr`] to validate input
+    pub fn parse_lenient(address: &str) -> Self {
+        address
+            .parse()
+            .unwrap_or_else(|_| Self::Unknown(address.to_string()))
+    }
+
+    /// Whether this is a phone number in the international form
+    pub fn is_global_phone_number(&self) -> bool {
+        matches!(self, Self::Plmn(n) if n.starts_with('+'))
+    }
+}
+
+/// Phone numbers are digits, optionally starting with a `+`, and may contain
+/// the separators `-` and `.`. National numbers may also contain `*` and `#`
+fn is_phone_number(number: &str) -> bool {
+    let digits = number.strip_prefix('+').unwrap_or(number);
+
+    digits.chars().any(|c| c.is_ascii_digit())
+        && digits
+            .chars()
+            .all(|c| matches!(c, '0'..='9' | '-' | '.' | '*' | '#'))
+}
+
+impl FromStr for Address {
+    type Err = &'static str;
+
+    /// Parse an address with a `/TYPE=` suffix, addresses without one are
+    /// assumed to be an email address if they contain an `@`, or a phone
+    /// number otherwise
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let (value, address_type) = match address.rfind("/TYPE=") {
+            Some(i) => (&address[..i], Some(&address[i + "/TYPE=".len()..])),
+            None => (address, None),
+        };
+
+        match address_type {
+            Some(t) if t.eq_ignore_ascii_case("PLMN") => {
+                if !is_phone_number(value) {
+                    return Err("Invalid phone number");
+                }
+                Ok(Self::Plmn(value.to_string()))
+            }
+            Some(t) if t.eq_ignore_ascii_case("IPv4") => value
+                .parse()
+                .map(Self::Ipv4)
+                .map_err(|_| "Invalid IPv4 address"),
+            Some(t) if t.eq_ignore_ascii_case("IPv6") => value
+                .parse()
+                .map(Self::Ipv6)
+                .map_err(|_| "Invalid IPv6 address"),
+            Some("") => Err("Address has an empty type"),
+            Some(t) => Ok(Self::UserDefined {
+                value: value.to_string(),
+                address_type: t.to_string(),
+            }),
+            None if value.contains('@') => Ok(Self::Email(value.to_string())),
+            None if is_phone_number(value) => Ok(Self::Plmn(value.to_string())),
+            None => Err("Address isn't a phone number or an email address"),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Plmn(number) => write!(f, "{}/TYPE=PLMN", number),
+            Self::Email(email) => write!(f, "{}", email),
+            Self::Ipv4(ip) => write!(f, "{}/TYPE=IPv4", ip),
+            Self::Ipv6(ip) => write!(f, "{}/TYPE=IPv6", ip),
+            Self::UserDefined {
+                value,
+                address_type,
+            } => write!(f, "{}/TYPE={}", value, address_type),
+            Self::Unknown(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for address in &[
+            "+15551234/TYPE=PLMN",
+            "05551234/TYPE=PLMN",
+            "user@example.com",
+            "192.0.2.1/TYPE=IPv4",
+            "2001:db8::1/TYPE=IPv6",
+            "john/TYPE=USER",
+        ] {
+            let parsed: Address = address.parse().unwrap();
+            assert_eq!(&parsed.to_string(), address);
+        }
+    }
+
+    #[test]
+    fn leading_zeros_and_short_codes() {
+        assert_eq!(
+            "0049301234".parse(),
+            Ok(Address::Plmn("0049301234".to_string()))
+        );
+        assert_eq!("*611".parse(), Ok(Address::Plmn("*611".to_string())));
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        assert!("abc".parse::<Address>().is_err());
+        assert!("abc/TYPE=PLMN".parse::<Address>().is_err());
+        assert!("300.1.1.1/TYPE=IPv4".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn lenient_addresses_are_kept() {
+        let address = Address::parse_lenient("Vodafone/TYPE=PLMN");
+
+        assert_eq!(address, Address::Unknown("Vodafone/TYPE=PLMN".to_string()));
+        assert_eq!(address.to_string(), "Vodafone/TYPE=PLMN");
+        assert_eq!(
+            Address::parse_lenient("+1/TYPE=PLMN"),
+            Address::Plmn("+1".to_string())
+        );
+    }
+}
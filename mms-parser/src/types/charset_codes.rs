@@ -0,0 +1,53 @@
+// MIBenum values from
+// https://www.iana.org/assignments/character-sets/character-sets.xhtml
+// limited to the charsets which can be decoded
+pub(crate) const CHARSET_CODES: [(u64, &str); 48] = [
+    (3, "US-ASCII"),
+    (4, "ISO-8859-1"),
+    (5, "ISO-8859-2"),
+    (6, "ISO-8859-3"),
+    (7, "ISO-8859-4"),
+    (8, "ISO-8859-5"),
+    (9, "ISO-8859-6"),
+    (10, "ISO-8859-7"),
+    (11, "ISO-8859-8"),
+    (12, "ISO-8859-9"),
+    (13, "ISO-8859-10"),
+    (17, "Shift_JIS"),
+    (18, "EUC-JP"),
+    (36, "KS_C_5601-1987"),
+    (38, "EUC-KR"),
+    (39, "ISO-2022-JP"),
+    (81, "ISO-8859-6-E"),
+    (82, "ISO-8859-6-I"),
+    (84, "ISO-8859-8-E"),
+    (85, "ISO-8859-8-I"),
+    (106, "UTF-8"),
+    (109, "ISO-8859-13"),
+    (110, "ISO-8859-14"),
+    (111, "ISO-8859-15"),
+    (112, "ISO-8859-16"),
+    (113, "GBK"),
+    (114, "GB18030"),
+    (1000, "ISO-10646-UCS-2"),
+    (1013, "UTF-16BE"),
+    (1014, "UTF-16LE"),
+    (1015, "UTF-16"),
+    (2025, "GB2312"),
+    (2026, "Big5"),
+    (2027, "macintosh"),
+    (2084, "KOI8-R"),
+    (2086, "IBM866"),
+    (2088, "KOI8-U"),
+    (2101, "Big5-HKSCS"),
+    (2109, "windows-874"),
+    (2250, "windows-1250"),
+    (2251, "windows-1251"),
+    (2252, "windows-1252"),
+    (2253, "windows-1253"),
+    (2254, "windows-1254"),
+    (2255, "windows-1255"),
+    (2256, "windows-1256"),
+    (2257, "windows-1257"),
+    (2258, "windows-1258"),
+];
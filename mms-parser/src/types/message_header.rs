@@ -1,16 +1,78 @@
+use std::time::SystemTime;
+
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 // TODO: this might be overkill, it might be easier to just translate
 // header bytes to string names, and create a hashmap of them
 pub enum MessageHeader {
     XWapApplicationId(usize),
-    // PushFlag,
-    // EncodingVersion,
+    PushFlag(u8),
+    EncodingVersion(String),
     ContentLength(usize),
-    // XWapInitiatorUri,
-    AcceptCharset(u8), // This should change to a charset struct
+    XWapInitiatorUri(String),
+    XWapContentUri(String),
+    AcceptCharset(AcceptCharsetValue),
     // AcceptRanges,
     ContentId(String),
     ContentLocation(String),
+    ContentBase(String),
+    ContentEncoding(String),
+    ContentLanguage(LanguageValue),
+    ContentMd5(Vec<u8>),
+    ContentDisposition(ContentDisposition),
+    Date(SystemTime),
+    Expires(SystemTime),
+    LastModified(SystemTime),
+    Etag(String),
+    /// A header which isn't in the WSP header table, as a name and value
+    ApplicationHeader((String, String)),
+    /// Headers after this one are from a different header code page, and are
+    /// kept as `UnknownHeader`s unless the page is 1, the default
+    ShiftCodePage(u8),
+    /// A header without a known encoding, as the header code and the encoded
+    /// value
     UnknownHeader((u8, Vec<u8>)),
 }
+
+/// Value of Accept-Charset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptCharsetValue {
+    pub charset: CharsetValue,
+    /// The Q-value in thousandths, `None` means 1
+    pub quality: Option<u16>,
+}
+
+/// A charset in a header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharsetValue {
+    Any,
+    /// The charset's MIBenum
+    Code(u64),
+    Name(String),
+}
+
+/// Value of Content-Language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LanguageValue {
+    Any,
+    /// A language from the table in WSP Appendix A
+    Code(u64),
+    Name(String),
+}
+
+/// Value of Content-Disposition, `disposition` is usually "attachment" or
+/// "inline"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDisposition {
+    pub disposition: String,
+    pub parameters: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    pub fn filename(&self) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(name, _)| name == "filename")
+            .map(|(_, value)| value.as_str())
+    }
+}
@@ -1,6 +1,6 @@
-use std::convert::TryFrom;
+use std::time::{Duration, SystemTime};
 
-use super::VndWapMmsMessage;
+use super::{Address, VndWapMmsMessage};
 
 pub(crate) type ShortUint = u8;
 pub(crate) type LongUint = u64;
@@ -9,19 +9,41 @@ pub(crate) type Bytes = Vec<u8>;
 pub(crate) type ContentType = mime::Mime;
 
 // TODO: parse all variants so this isn't necessary
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MmsHeaderValue {
     Bool(bool),
     LongUint(u64),
     ShortUint(u8),
     String(String),
     Bytes(Vec<u8>),
+    Address(Address),
     FromField(FromField),
     ContentType(mime::Mime),
+    SystemTime(SystemTime),
     ExpiryField(ExpiryField),
     ClassIdentifier(ClassIdentifier),
     MessageTypeField(MessageTypeField),
     RetrieveStatusField(RetrieveStatusField),
+    ResponseStatusField(ResponseStatusField),
+    StatusField(StatusField),
+    PriorityField(PriorityField),
+    SenderVisibilityField(SenderVisibilityField),
+    ReadStatusField(ReadStatusField),
+    YesNoField(YesNoField),
+    ReplyChargingField(ReplyChargingField),
+    MMStateField(MMStateField),
+    StoreStatusField(StoreStatusField),
+    RetrievalModeField(RetrievalModeField),
+    ContentClassField(ContentClassField),
+    CancelStatusField(CancelStatusField),
+    PreviouslySentBy(PreviouslySentBy),
+    PreviouslySentDate(PreviouslySentDate),
+    MMFlagsField(MMFlagsField),
+    MboxQuantityField(MboxQuantityField),
+    ElementDescriptor(ElementDescriptor),
+    IndexedResponseStatus(IndexedResponseStatus),
+    IndexedString(IndexedString),
+    MmsHeader(MmsHeader),
 }
 
 macro_rules! mms_header_from {
@@ -39,20 +61,70 @@ mms_header_from!(LongUint, u64);
 mms_header_from!(ShortUint, u8);
 mms_header_from!(String, String);
 mms_header_from!(Bytes, Vec<u8>);
+mms_header_from!(Address, Address);
 mms_header_from!(FromField, FromField);
 mms_header_from!(ContentType, ContentType);
+mms_header_from!(SystemTime, SystemTime);
 mms_header_from!(ExpiryField, ExpiryField);
 mms_header_from!(ClassIdentifier, ClassIdentifier);
 mms_header_from!(MessageTypeField, MessageTypeField);
 mms_header_from!(RetrieveStatusField, RetrieveStatusField);
+mms_header_from!(ResponseStatusField, ResponseStatusField);
+mms_header_from!(StatusField, StatusField);
+mms_header_from!(PriorityField, PriorityField);
+mms_header_from!(SenderVisibilityField, SenderVisibilityField);
+mms_header_from!(ReadStatusField, ReadStatusField);
+mms_header_from!(YesNoField, YesNoField);
+mms_header_from!(ReplyChargingField, ReplyChargingField);
+mms_header_from!(MMStateField, MMStateField);
+mms_header_from!(StoreStatusField, StoreStatusField);
+mms_header_from!(RetrievalModeField, RetrievalModeField);
+mms_header_from!(ContentClassField, ContentClassField);
+mms_header_from!(CancelStatusField, CancelStatusField);
+mms_header_from!(PreviouslySentBy, PreviouslySentBy);
+mms_header_from!(PreviouslySentDate, PreviouslySentDate);
+mms_header_from!(MMFlagsField, MMFlagsField);
+mms_header_from!(MboxQuantityField, MboxQuantityField);
+mms_header_from!(ElementDescriptor, ElementDescriptor);
+mms_header_from!(IndexedResponseStatus, IndexedResponseStatus);
+mms_header_from!(IndexedString, IndexedString);
+mms_header_from!(MmsHeader, MmsHeader);
+
+/// Generates the getter for a header, headers marked `repeated` can appear
+/// more than once, and their getter returns every value. Values of another
+/// type, such as the [`Indexed`] ones in a M-Delete.conf, are skipped
+macro_rules! header_getter {
+    ($name:ident, $camel_name:ident, $under_name:ident, $type:ident) => {
+        pub fn $under_name(&self) -> Option<&$type> {
+            match self.headers.get(&$name::$camel_name) {
+                Some(MmsHeaderValue::$type(d)) => Some(d),
+                _ => None,
+            }
+        }
+    };
+    ($name:ident, $camel_name:ident, $under_name:ident, $type:ident, repeated) => {
+        pub fn $under_name(&self) -> Vec<$type> {
+            self.headers
+                .get_all(&$name::$camel_name)
+                .filter_map(|v| match v {
+                    MmsHeaderValue::$type(d) => Some(d.clone()),
+                    _ => None,
+                })
+                .collect()
+        }
+    };
+}
 
 // TODO: Generalize this
 macro_rules! header_fields {
+    (@repeated repeated) => { true };
+    (@repeated) => { false };
     ($name:ident, $((
                 $camel_name:ident,
                 $under_name:ident,
                 $type:ident,
-                $binary_code:expr));+$(;)*) => {
+                $binary_code:expr
+                $(, $repeated:ident)?));+$(;)*) => {
         #[derive(Debug, Hash, PartialEq, Eq, Clone)]
         pub enum $name {
             $(
@@ -79,102 +151,112 @@ macro_rules! header_fields {
                     $(
                         Self::$camel_name => vec![$binary_code | 0x80],
                     )+
-                        Self::UnknownInt(i) => vec![i],
+                        Self::UnknownInt(i) => vec![i | 0x80],
                         Self::ImplicitBody => Vec::new(),
                 }
             }
         }
 
-        impl VndWapMmsMessage {
-            $(
-                pub fn $under_name(&self) -> Option<&$type> {
-                    match self.headers.get(&$name::$camel_name) {
-                        Some(v) => match v {
-                            MmsHeaderValue::$type(d) => Some(d),
-                            u => panic!("Unexpected value in $camel_name: {:?}", u)
-                        },
-                        None => None
-                    }
+        impl $name {
+            /// Whether the header may appear more than once in a PDU, every
+            /// other header is single valued
+            pub fn is_repeatable(&self) -> bool {
+                match self {
+                    $(
+                        Self::$camel_name => header_fields!(@repeated $($repeated)?),
+                    )+
+                        Self::UnknownInt(_) => true,
+                        Self::ImplicitBody => false,
                 }
+            }
+        }
+
+        impl VndWapMmsMessage<'_> {
+            $(
+                header_getter!(
+                    $name, $camel_name, $under_name, $type $(, $repeated)?
+                );
             )+
         }
     }
 }
 
 // TODO: It may be necessary to have a unknown field for encoding messages
+// From OMA-TS-MMS_ENC-V1_3 table 27
 header_fields! {
     MmsHeader,
-    (Bcc, bcc, String, 0x01);
-    (Cc, cc, String, 0x02);
-    // (Content);
+    (Bcc, bcc, Address, 0x01, repeated);
+    (Cc, cc, Address, 0x02, repeated);
+    (XMmsContentLocation, x_mms_content_location, String, 0x03, repeated);
     (ContentType, content_type, ContentType, 0x04);
-    (Date, date, LongUint, 0x05);
-    (From, from, String, 0x09);
-    (MessageID, message_id, String, 0x0B);
-    // (Mode);
-    (Subject, subject, String, 0x16);
-    (To, to, String, 0x17);
-    (XMmsAdaptationAllowed, x_mms_adaptation_alowed, Bool, 0x3C);
-    // (XMmsApplicID);
-    // (XMmsAttributes);
-    // (XMmsAuxApplicInfo);
-    // (XMmsCancelID);
-    // (XMmsCancelStatus);
-    // (XMmsContentClass);
-    (XMmsContentLocation, x_mms_content_location, String, 0x03);
-    // (XMmsDRMContent);
-    (XMmsDeliveryReport, x_mms_delivery_report, Bool, 0x06);
-    // (XMmsDeliveryTime);
-    // (XMmsDistributionIndicator);
-    // (XMmsElementDescriptor);
+    (Date, date, SystemTime, 0x05);
+    (XMmsDeliveryReport, x_mms_delivery_report, YesNoField, 0x06);
+    (XMmsDeliveryTime, x_mms_delivery_time, ExpiryField, 0x07);
     (XMmsExpiry, x_mms_expiry, ExpiryField, 0x08);
-    (XMmsLimit, x_mms_limit, LongUint, 0x33);
-    // (XMmsMMFlags);
-    (XMmsMMSVersion, x_mms_mms_version, ShortUint, 0x0D);
-    // (XMmsMMState);
-    // (XMmsMboxQuotas);
-    // (XMmsMboxTotals);
+    (From, from, FromField, 0x09);
     (XMmsMessageClass, x_mms_message_class, ClassIdentifier, 0x0A);
-    // (XMmsMessageCount);
-    (XMmsMessageSize, x_mms_message_size, LongUint, 0x0E);
+    (MessageID, message_id, String, 0x0B);
     (XMmsMessageType, x_mms_message_type, MessageTypeField, 0x0C);
-    // (XMmsPreviouslySentBy);
-    // (XMmsPreviouslySentDate);
-    (XMmsPriority, x_mms_priority, ShortUint, 0x0F);
-    // (XMmsQuotas);
-    (XMmsReadReport, x_mms_read_report, Bool, 0x10);
-    // (XMmsReadStatus);
-    // (XMmsRecommendedRe);
-    // (XMmsRecommendedRetrieval);
-    // (XMmsReplaceID);
-    // (XMmsReplyApplicID);
-    // (XMmsReplyCharging);
-    // (XMmsReplyChargingDeadline);
-    // (XMmsReplyChargingID);
-    // (XMmsReplyChargingSize);
-    // (XMmsReportAllowed);
-    (XMmsResponseStatus, x_mms_response_status, Bytes, 0x12);
-    (XMmsResponseText, x_mms_response_text, String, 0x13);
+    (XMmsMMSVersion, x_mms_mms_version, ShortUint, 0x0D);
+    (XMmsMessageSize, x_mms_message_size, LongUint, 0x0E);
+    (XMmsPriority, x_mms_priority, PriorityField, 0x0F);
+    (XMmsReadReport, x_mms_read_report, YesNoField, 0x10);
+    (XMmsReportAllowed, x_mms_report_allowed, Bool, 0x11);
+    (XMmsResponseStatus, x_mms_response_status, ResponseStatusField, 0x12, repeated);
+    (XMmsResponseText, x_mms_response_text, String, 0x13, repeated);
+    (XMmsSenderVisibility, x_mms_sender_visibility, SenderVisibilityField, 0x14);
+    (XMmsStatus, x_mms_status, StatusField, 0x15);
+    (Subject, subject, String, 0x16);
+    (To, to, Address, 0x17, repeated);
+    (XMmsTransactionId, x_mms_transaction_id, String, 0x18);
     (XMmsRetrieveStatus, x_mms_retrieve_status, RetrieveStatusField, 0x19);
-    // (XMmsRetrieveText);
-    // (XMmsSenderVisibility);
-    // (XMmsStart);
-    // (XMmsStatus);
-    // (XMmsStore);
-    // (XMmsStoreStatus);
-    // (XMmsStoreStatusText);
-    // (XMmsStored);
-    // (XMmsTotals);
-    (XMmsTransactionId, x_mms_transaction_id, String, 0x18)
+    (XMmsRetrieveText, x_mms_retrieve_text, String, 0x1A);
+    (XMmsReadStatus, x_mms_read_status, ReadStatusField, 0x1B);
+    (XMmsReplyCharging, x_mms_reply_charging, ReplyChargingField, 0x1C);
+    (XMmsReplyChargingDeadline, x_mms_reply_charging_deadline, ExpiryField, 0x1D);
+    (XMmsReplyChargingID, x_mms_reply_charging_id, String, 0x1E);
+    (XMmsReplyChargingSize, x_mms_reply_charging_size, LongUint, 0x1F);
+    (XMmsPreviouslySentBy, x_mms_previously_sent_by, PreviouslySentBy, 0x20, repeated);
+    (XMmsPreviouslySentDate, x_mms_previously_sent_date, PreviouslySentDate, 0x21, repeated);
+    (XMmsStore, x_mms_store, Bool, 0x22);
+    (XMmsMMState, x_mms_mm_state, MMStateField, 0x23, repeated);
+    (XMmsMMFlags, x_mms_mm_flags, MMFlagsField, 0x24, repeated);
+    (XMmsStoreStatus, x_mms_store_status, StoreStatusField, 0x25);
+    (XMmsStoreStatusText, x_mms_store_status_text, String, 0x26);
+    (XMmsStored, x_mms_stored, Bool, 0x27);
+    (XMmsAttributes, x_mms_attributes, MmsHeader, 0x28, repeated);
+    (XMmsTotals, x_mms_totals, Bool, 0x29);
+    (XMmsMboxTotals, x_mms_mbox_totals, MboxQuantityField, 0x2A, repeated);
+    (XMmsQuotas, x_mms_quotas, Bool, 0x2B);
+    (XMmsMboxQuotas, x_mms_mbox_quotas, MboxQuantityField, 0x2C, repeated);
+    (XMmsMessageCount, x_mms_message_count, LongUint, 0x2D);
+    (Content, content, Bytes, 0x2E);
+    (XMmsStart, x_mms_start, LongUint, 0x2F);
+    (AdditionalHeaders, additional_headers, Bytes, 0x30);
+    (XMmsDistributionIndicator, x_mms_distribution_indicator, Bool, 0x31);
+    (XMmsElementDescriptor, x_mms_element_descriptor, ElementDescriptor, 0x32, repeated);
+    (XMmsLimit, x_mms_limit, LongUint, 0x33);
+    (XMmsRecommendedRetrievalMode, x_mms_recommended_retrieval_mode, RetrievalModeField, 0x34);
+    (XMmsRecommendedRetrievalModeText, x_mms_recommended_retrieval_mode_text, String, 0x35);
+    (XMmsStatusText, x_mms_status_text, String, 0x36);
+    (XMmsApplicID, x_mms_applic_id, String, 0x37);
+    (XMmsReplyApplicID, x_mms_reply_applic_id, String, 0x38);
+    (XMmsAuxApplicInfo, x_mms_aux_applic_info, String, 0x39);
+    (XMmsContentClass, x_mms_content_class, ContentClassField, 0x3A);
+    (XMmsDRMContent, x_mms_drm_content, Bool, 0x3B);
+    (XMmsAdaptationAllowed, x_mms_adaptation_alowed, YesNoField, 0x3C);
+    (XMmsReplaceID, x_mms_replace_id, String, 0x3D);
+    (XMmsCancelID, x_mms_cancel_id, String, 0x3E);
+    (XMmsCancelStatus, x_mms_cancel_status, CancelStatusField, 0x3F)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FromField {
-    Address(String),
+    Address(Address),
     InsertAddress,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ClassIdentifier {
     Personal,
     Advertisment,
@@ -183,118 +265,381 @@ pub enum ClassIdentifier {
     Other(String),
 }
 
-// TODO: use date time and time deltas for this (probably from chrono crate)
-// or even better convert realitve time to absolute when parsing, and just store that
-#[derive(Debug, Clone)]
+/// Value of X-Mms-Expiry, X-Mms-Delivery-Time, and
+/// X-Mms-Reply-Charging-Deadline, either a point in time, or a delay from when
+/// the message was received
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExpiryField {
-    Absolute(u64),
-    Relative(u64),
+    Absolute(SystemTime),
+    Relative(Duration),
+}
+
+impl ExpiryField {
+    /// The point in time this field refers to, relative values are counted
+    /// from `received`, the time the message containing the field arrived.
+    /// Returns `None` if a relative value is too large to be represented
+    pub fn to_absolute(&self, received: SystemTime) -> Option<SystemTime> {
+        match self {
+            Self::Absolute(t) => Some(*t),
+            Self::Relative(d) => received.checked_add(*d),
+        }
+    }
+}
+
+/// Value of X-Mms-Previously-Sent-By, `forward_count` is how many times the
+/// message has been forwarded before reaching `address`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviouslySentBy {
+    pub forward_count: u64,
+    pub address: Address,
+}
+
+/// Value of X-Mms-Previously-Sent-Date, the date the message was sent for
+/// the `forward_count`th time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviouslySentDate {
+    pub forward_count: u64,
+    pub date: SystemTime,
+}
+
+/// Value of X-Mms-MM-Flags, a keyword to add to, remove from, or filter the
+/// messages in the MMBox by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MMFlagsField {
+    Add(String),
+    Remove(String),
+    Filter(String),
+}
+
+/// Value of X-Mms-Mbox-Totals and X-Mms-Mbox-Quotas, either a number of
+/// messages, or a size in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MboxQuantityField {
+    Messages(u64),
+    Size(u64),
+}
+
+/// Value of X-Mms-Element-Descriptor, a reference to a part of the message
+/// followed by it's parameters as name, value pairs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementDescriptor {
+    pub content_reference: String,
+    pub parameters: Vec<(String, String)>,
+}
+
+/// Value of X-Mms-Response-Status, X-Mms-Response-Text and
+/// X-Mms-Content-Location in a M-Delete.conf or M-Mbox-Delete.conf, `index`
+/// is the position of the X-Mms-Content-Location in the request it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indexed<T> {
+    pub index: u64,
+    pub value: T,
+}
+
+pub type IndexedResponseStatus = Indexed<ResponseStatusField>;
+pub type IndexedString = Indexed<String>;
+
+// From OMA-TS-MMS_ENC-V1_3 section 7.3
+token_enum! {
+    /// Value of X-Mms-Message-Type
+    MessageTypeField {
+        MSendReq = 128 => "m-send-req",
+        MSendConf = 129 => "m-send-conf",
+        MNotificationInd = 130 => "m-notification-ind",
+        MNotifyrespInd = 131 => "m-notifyresp-ind",
+        MRetrieveConf = 132 => "m-retrieve-conf",
+        MAcknowledgeInd = 133 => "m-acknowledge-ind",
+        MDeliveryInd = 134 => "m-delivery-ind",
+        MReadRecInd = 135 => "m-read-rec-ind",
+        MReadOrigInd = 136 => "m-read-orig-ind",
+        MForwardReq = 137 => "m-forward-req",
+        MForwardConf = 138 => "m-forward-conf",
+        MMboxStoreReq = 139 => "m-mbox-store-req",
+        MMboxStoreConf = 140 => "m-mbox-store-conf",
+        MMboxViewReq = 141 => "m-mbox-view-req",
+        MMboxViewConf = 142 => "m-mbox-view-conf",
+        MMboxUploadReq = 143 => "m-mbox-upload-req",
+        MMboxUploadConf = 144 => "m-mbox-upload-conf",
+        MMboxDeleteReq = 145 => "m-mbox-delete-req",
+        MMboxDeleteConf = 146 => "m-mbox-delete-conf",
+        MMboxDescr = 147 => "m-mbox-descr",
+        MDeleteReq = 148 => "m-delete-req",
+        MDeleteConf = 149 => "m-delete-conf",
+        MCancelReq = 150 => "m-cancel-req",
+        MCancelConf = 151 => "m-cancel-conf",
+    }
+
+    /// Value of X-Mms-Retrieve-Status
+    RetrieveStatusField {
+        Ok = 128 => "Ok",
+        ErrorTransientFailure = 192 => "Error-transient-failure",
+        ErrorTransientMessageNotFound = 193
+            => "Error-transient-message-not-found",
+        ErrorTransientNetworkProblem = 194
+            => "Error-transient-network-problem",
+        ErrorPermanentFailure = 224 => "Error-permanent-failure",
+        ErrorPermanentServceDenied = 225 => "Error-permanent-service-denied",
+        ErrorPermanentMessageNotFound = 226
+            => "Error-permanent-message-not-found",
+        ErrorPermanentContentUnsupported = 227
+            => "Error-permanent-content-unsupported",
+    }
+
+    /// Value of X-Mms-Response-Status. The errors from 129 to 136 are from
+    /// MMS 1.0 and are obsolete, but may still be sent by older MMSCs
+    ResponseStatusField {
+        Ok = 128 => "Ok",
+        ErrorUnspecified = 129 => "Error-unspecified",
+        ErrorServiceDenied = 130 => "Error-service-denied",
+        ErrorMessageFormatCorrupt = 131 => "Error-message-format-corrupt",
+        ErrorSendingAddressUnresolved = 132
+            => "Error-sending-address-unresolved",
+        ErrorMessageNotFound = 133 => "Error-message-not-found",
+        ErrorNetworkProblem = 134 => "Error-network-problem",
+        ErrorContentNotAccepted = 135 => "Error-content-not-accepted",
+        ErrorUnsupportedMessage = 136 => "Error-unsupported-message",
+        ErrorTransientFailure = 192 => "Error-transient-failure",
+        ErrorTransientSendingAddressUnresolved = 193
+            => "Error-transient-sending-address-unresolved",
+        ErrorTransientMessageNotFound = 194
+            => "Error-transient-message-not-found",
+        ErrorTransientNetworkProblem = 195
+            => "Error-transient-network-problem",
+        ErrorTransientPartialSuccess = 196
+            => "Error-transient-partial-success",
+        ErrorPermanentFailure = 224 => "Error-permanent-failure",
+        ErrorPermanentServiceDenied = 225 => "Error-permanent-service-denied",
+        ErrorPermanentMessageFormatCorrupt = 226
+            => "Error-permanent-message-format-corrupt",
+        ErrorPermanentSendingAddressUnresolved = 227
+            => "Error-permanent-sending-address-unresolved",
+        ErrorPermanentMessageNotFound = 228
+            => "Error-permanent-message-not-found",
+        ErrorPermanentContentNotAccepted = 229
+            => "Error-permanent-content-not-accepted",
+        ErrorPermanentReplyChargingLimitationsNotMet = 230
+            => "Error-permanent-reply-charging-limitations-not-met",
+        ErrorPermanentReplyChargingRequestNotAccepted = 231
+            => "Error-permanent-reply-charging-request-not-accepted",
+        ErrorPermanentReplyChargingForwardingDenied = 232
+            => "Error-permanent-reply-charging-forwarding-denied",
+        ErrorPermanentReplyChargingNotSupported = 233
+            => "Error-permanent-reply-charging-not-supported",
+        ErrorPermanentAddressHidingNotSupported = 234
+            => "Error-permanent-address-hiding-not-supported",
+        ErrorPermanentLackOfPrepaid = 235 => "Error-permanent-lack-of-prepaid",
+    }
+
+    /// Value of X-Mms-Status, the state of a message in a M-NotifyResp.ind
+    /// or a delivery report
+    StatusField {
+        Expired = 128 => "Expired",
+        Retrieved = 129 => "Retrieved",
+        Rejected = 130 => "Rejected",
+        Deferred = 131 => "Deferred",
+        Unrecognised = 132 => "Unrecognised",
+        Indeterminate = 133 => "Indeterminate",
+        Forwarded = 134 => "Forwarded",
+        Unreachable = 135 => "Unreachable",
+    }
+
+    /// Value of X-Mms-Priority
+    PriorityField {
+        Low = 128 => "Low",
+        Normal = 129 => "Normal",
+        High = 130 => "High",
+    }
+
+    /// Value of X-Mms-Sender-Visibility, whether the recipient is shown the
+    /// sender's address
+    SenderVisibilityField {
+        Hide = 128 => "Hide",
+        Show = 129 => "Show",
+    }
+
+    /// Value of yes or no headers which carriers have been seen sending other
+    /// values in, such as X-Mms-Delivery-Report
+    YesNoField {
+        Yes = 128 => "Yes",
+        No = 129 => "No",
+    }
+
+    /// Value of X-Mms-Read-Status in a read report
+    ReadStatusField {
+        Read = 128 => "Read",
+        DeletedWithoutBeingRead = 129 => "Deleted without being read",
+    }
+
+    /// Value of X-Mms-Reply-Charging, whether the sender pays for the reply
+    ReplyChargingField {
+        Requested = 128 => "Requested",
+        RequestedTextOnly = 129 => "Requested text only",
+        Accepted = 130 => "Accepted",
+        AcceptedTextOnly = 131 => "Accepted text only",
+    }
+
+    /// Value of X-Mms-MM-State, the state of a message in the MMBox
+    MMStateField {
+        Draft = 128 => "Draft",
+        Sent = 129 => "Sent",
+        New = 130 => "New",
+        Retrieved = 131 => "Retrieved",
+        Forwarded = 132 => "Forwarded",
+    }
+
+    /// Value of X-Mms-Store-Status, whether a message was stored in the MMBox
+    StoreStatusField {
+        Success = 128 => "Success",
+        ErrorTransientFailure = 192 => "Error-transient-failure",
+        ErrorTransientNetworkProblem = 193
+            => "Error-transient-network-problem",
+        ErrorPermanentFailure = 224 => "Error-permanent-failure",
+        ErrorPermanentServiceDenied = 225 => "Error-permanent-service-denied",
+        ErrorPermanentMessageFormatCorrupt = 226
+            => "Error-permanent-message-format-corrupt",
+        ErrorPermanentMessageNotFound = 227
+            => "Error-permanent-message-not-found",
+        ErrorPermanentMmboxFull = 228 => "Error-permanent-mmbox-full",
+    }
+
+    /// Value of X-Mms-Recommended-Retrieval-Mode
+    RetrievalModeField {
+        Manual = 128 => "Manual",
+    }
+
+    /// Value of X-Mms-Content-Class, the kinds of content a message has
+    ContentClassField {
+        Text = 128 => "text",
+        ImageBasic = 129 => "image-basic",
+        ImageRich = 130 => "image-rich",
+        VideoBasic = 131 => "video-basic",
+        VideoRich = 132 => "video-rich",
+        Megapixel = 133 => "megapixel",
+        ContentBasic = 134 => "content-basic",
+        ContentRich = 135 => "content-rich",
+    }
+
+    /// Value of X-Mms-Cancel-Status
+    CancelStatusField {
+        /// The M-Cancel.req was received
+        Received = 128 => "Cancel Request Successfully received",
+        Corrupted = 129 => "Cancel Request corrupted",
+    }
 }
 
-#[derive(Debug, Clone)]
-pub enum MessageTypeField {
-    MSendReq,
-    MSendConf,
-    MNotificationInd,
-    MNotifyrespInd,
-    MRetrieveConf,
-    MAcknowledgeInd,
-    MDeliveryInd,
-    MReadRecInd,
-    MReadOrigInd,
-    MForwardReq,
-    MForwardConf,
-    MMboxStoreReq,
-    MMboxStoreConf,
-    MMboxViewReq,
-    MMboxViewConf,
-    MMboxUploadReq,
-    MMboxUploadConf,
-    MMboxDeleteReq,
-    MMboxDeleteConf,
-    MMboxDescr,
-    MDeleteReq,
-    MDeleteConf,
-    MCancelReq,
-    MCancelConf,
+/// Status values from 192 to 223 are transient errors, and from 224 to 255
+/// are permanent errors, including values which aren't defined yet
+fn is_transient_error(status: u8) -> bool {
+    (192..=223).contains(&status)
 }
 
-#[derive(Debug, Clone)]
-pub enum RetrieveStatusField {
-    Ok,
-    ErrorTransientFailure,
-    ErrorTransientFailureOther(u8),
-    ErrorTransientMessageNotFound,
-    ErrorTransientNetworkProblem,
-    ErrorPermanentFailure,
-    ErrorPermanentFailureOther(u8),
-    ErrorPermanentServceDenied,
-    ErrorPermanentMessageNotFound,
-    ErrorPermanentContentUnsupported,
+fn is_permanent_error(status: u8) -> bool {
+    status >= 224
 }
 
-// TODO: Use a macro instead of manually writing impl TryFrom and Into
-impl TryFrom<u8> for MessageTypeField {
-    type Error = &'static str;
-
-    fn try_from(i: u8) -> Result<Self, &'static str> {
-        match i {
-            128 => Ok(MessageTypeField::MSendReq),
-            129 => Ok(MessageTypeField::MSendConf),
-            130 => Ok(MessageTypeField::MNotificationInd),
-            131 => Ok(MessageTypeField::MNotifyrespInd),
-            132 => Ok(MessageTypeField::MRetrieveConf),
-            133 => Ok(MessageTypeField::MAcknowledgeInd),
-            134 => Ok(MessageTypeField::MDeliveryInd),
-            135 => Ok(MessageTypeField::MReadRecInd),
-            136 => Ok(MessageTypeField::MReadOrigInd),
-            137 => Ok(MessageTypeField::MForwardReq),
-            138 => Ok(MessageTypeField::MForwardConf),
-            139 => Ok(MessageTypeField::MMboxStoreReq),
-            140 => Ok(MessageTypeField::MMboxStoreConf),
-            141 => Ok(MessageTypeField::MMboxViewReq),
-            142 => Ok(MessageTypeField::MMboxViewConf),
-            143 => Ok(MessageTypeField::MMboxUploadReq),
-            144 => Ok(MessageTypeField::MMboxUploadConf),
-            145 => Ok(MessageTypeField::MMboxDeleteReq),
-            146 => Ok(MessageTypeField::MMboxDeleteConf),
-            147 => Ok(MessageTypeField::MMboxDescr),
-            148 => Ok(MessageTypeField::MDeleteReq),
-            149 => Ok(MessageTypeField::MDeleteConf),
-            150 => Ok(MessageTypeField::MCancelReq),
-            151 => Ok(MessageTypeField::MCancelConf),
-            _ => Err("Unknown value for X-Mms-Message-Type"),
+impl RetrieveStatusField {
+    /// Whether fetching the message failed, but may succeed if it's tried
+    /// again later
+    pub fn is_transient(&self) -> bool {
+        is_transient_error((*self).into())
+    }
+
+    /// Whether fetching the message failed, and trying again won't help
+    pub fn is_permanent(&self) -> bool {
+        is_permanent_error((*self).into())
+    }
+}
+
+impl ResponseStatusField {
+    /// Whether the request failed, but may succeed if it's sent again later
+    pub fn is_transient(&self) -> bool {
+        *self == Self::ErrorNetworkProblem || is_transient_error((*self).into())
+    }
+
+    /// Whether the request failed, and sending it again won't help
+    pub fn is_permanent(&self) -> bool {
+        let status: u8 = (*self).into();
+        (129..=136).contains(&status) && !self.is_transient()
+            || is_permanent_error(status)
+    }
+}
+
+impl From<bool> for YesNoField {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::Yes
+        } else {
+            Self::No
         }
     }
 }
 
-impl Into<u8> for MessageTypeField {
-    fn into(self) -> u8 {
-        match self {
-            MessageTypeField::MSendReq => 128,
-            MessageTypeField::MSendConf => 129,
-            MessageTypeField::MNotificationInd => 130,
-            MessageTypeField::MNotifyrespInd => 131,
-            MessageTypeField::MRetrieveConf => 132,
-            MessageTypeField::MAcknowledgeInd => 133,
-            MessageTypeField::MDeliveryInd => 134,
-            MessageTypeField::MReadRecInd => 135,
-            MessageTypeField::MReadOrigInd => 136,
-            MessageTypeField::MForwardReq => 137,
-            MessageTypeField::MForwardConf => 138,
-            MessageTypeField::MMboxStoreReq => 139,
-            MessageTypeField::MMboxStoreConf => 140,
-            MessageTypeField::MMboxViewReq => 141,
-            MessageTypeField::MMboxViewConf => 142,
-            MessageTypeField::MMboxUploadReq => 143,
-            MessageTypeField::MMboxUploadConf => 144,
-            MessageTypeField::MMboxDeleteReq => 145,
-            MessageTypeField::MMboxDeleteConf => 146,
-            MessageTypeField::MMboxDescr => 147,
-            MessageTypeField::MDeleteReq => 148,
-            MessageTypeField::MDeleteConf => 149,
-            MessageTypeField::MCancelReq => 150,
-            MessageTypeField::MCancelConf => 151,
+impl YesNoField {
+    /// Whether the value is yes, unknown values are treated as no
+    pub fn is_yes(&self) -> bool {
+        *self == Self::Yes
+    }
+}
+
+impl StatusField {
+    /// Whether the message hasn't been delivered yet, but still may be
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Deferred | Self::Indeterminate)
+    }
+
+    /// Whether the message will never be delivered
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            Self::Expired
+                | Self::Rejected
+                | Self::Unrecognised
+                | Self::Unreachable
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn repeated_getters_return_every_value() {
+        let message = crate::parse_mms_pdu(
+            b"\x8C\x91\x98t\0\x8D\x92\x83http://mmsc/a\0\x83http://mmsc/b\0",
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.x_mms_content_location(),
+            ["http://mmsc/a", "http://mmsc/b"]
+        );
+        assert!(MmsHeader::XMmsContentLocation.is_repeatable());
+        assert!(MmsHeader::To.is_repeatable());
+        assert!(!MmsHeader::Subject.is_repeatable());
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        for i in 0..=255u8 {
+            let message_type = MessageTypeField::from(i);
+            assert_eq!(u8::from(message_type), i);
+            assert_eq!(message_type.to_string().parse(), Ok(message_type));
+
+            let status = ResponseStatusField::from(i);
+            assert_eq!(u8::from(status), i);
+            assert_eq!(status.to_string().parse(), Ok(status));
         }
     }
+
+    #[test]
+    fn tokens_display_spec_names() {
+        assert_eq!(MessageTypeField::MSendReq.to_string(), "m-send-req");
+        assert_eq!(
+            "M-Retrieve-Conf".parse(),
+            Ok(MessageTypeField::MRetrieveConf)
+        );
+        assert_eq!(MessageTypeField::Unknown(5).to_string(), "5");
+        assert!("m-send".parse::<MessageTypeField>().is_err());
+    }
 }
@@ -1,11 +1,139 @@
 use super::message_header::MessageHeader;
+use crate::{
+    charset::{charset_code, decode},
+    Charset,
+};
 use mime::Mime;
+use std::borrow::Cow;
 
-pub type MultiPartBody = Vec<MultiPartItem>;
+/// The parts of a multipart body, parts may contain multipart bodies of their
+/// own. Part data borrows from the data the body was parsed from
+#[derive(Debug, Clone)]
+pub struct MultiPartBody<'a> {
+    pub parts: Vec<MultiPartItem<'a>>,
+    /// Whether the body starts with the number of parts, which is optional
+    /// since WAP 1.3
+    pub has_num_entries: bool,
+}
+
+impl<'a> MultiPartBody<'a> {
+    pub fn new(parts: Vec<MultiPartItem<'a>>) -> Self {
+        Self {
+            parts,
+            has_num_entries: true,
+        }
+    }
+
+    pub fn without_num_entries(parts: Vec<MultiPartItem<'a>>) -> Self {
+        Self {
+            parts,
+            has_num_entries: false,
+        }
+    }
+
+    /// Every part which isn't a multipart body itself, depth first
+    pub fn leaves(&self) -> Vec<&MultiPartItem<'a>> {
+        self.parts
+            .iter()
+            .flat_map(|part| match &part.body {
+                PartBody::Data(_) => vec![part],
+                PartBody::MultiPart(body) => body.leaves(),
+            })
+            .collect()
+    }
+
+    /// Copy any borrowed data, so the body no longer borrows from the data it
+    /// was parsed from
+    pub fn into_owned(self) -> MultiPartBody<'static> {
+        MultiPartBody {
+            parts: self.parts.into_iter().map(|p| p.into_owned()).collect(),
+            has_num_entries: self.has_num_entries,
+        }
+    }
+}
+
+impl Default for MultiPartBody<'_> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct MultiPartItem {
+pub enum PartBody<'a> {
+    Data(Cow<'a, [u8]>),
+    MultiPart(MultiPartBody<'a>),
+}
+
+impl<'a> PartBody<'a> {
+    /// The data of a part, or `None` if it's a multipart body
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Data(d) => Some(d),
+            Self::MultiPart(_) => None,
+        }
+    }
+
+    pub fn into_owned(self) -> PartBody<'static> {
+        match self {
+            Self::Data(d) => PartBody::Data(Cow::Owned(d.into_owned())),
+            Self::MultiPart(body) => PartBody::MultiPart(body.into_owned()),
+        }
+    }
+}
+
+impl From<Vec<u8>> for PartBody<'_> {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Data(Cow::Owned(data))
+    }
+}
+
+impl<'a> From<&'a [u8]> for PartBody<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Self::Data(Cow::Borrowed(data))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiPartItem<'a> {
     pub content_type: Mime,
     pub headers: Vec<MessageHeader>,
-    pub body: Vec<u8>,
+    pub body: PartBody<'a>,
+}
+
+impl MultiPartItem<'_> {
+    /// The data of the part as text, decoded with the charset from it's
+    /// content type, or UTF-8 if it doesn't have one. Only text which isn't
+    /// UTF-8 is copied
+    pub fn text(&self) -> Option<Cow<'_, str>> {
+        let data = self.body.data()?;
+
+        let code = match self.content_type.get_param(mime::CHARSET) {
+            Some(name) => charset_code(name.as_str()),
+            None => None,
+        };
+        match code {
+            Some(code)
+                if code != Charset::UTF_8.mib_enum()
+                    && code != Charset::US_ASCII.mib_enum() =>
+            {
+                decode(code, data).ok().map(Cow::Owned)
+            }
+            _ => Some(String::from_utf8_lossy(data)),
+        }
+    }
+
+    pub fn into_owned(self) -> MultiPartItem<'static> {
+        MultiPartItem {
+            content_type: self.content_type,
+            headers: self.headers,
+            body: self.body.into_owned(),
+        }
+    }
+}
+
+/// Whether a body with this content type is a wap multipart body
+pub fn is_multipart(content_type: &Mime) -> bool {
+    content_type
+        .essence_str()
+        .starts_with("application/vnd.wap.multipart")
 }
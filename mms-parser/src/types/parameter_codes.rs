@@ -0,0 +1,53 @@
+use ParameterValue::*;
+
+/// How the value of a well known parameter is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParameterValue {
+    QValue,
+    WellKnownCharset,
+    VersionValue,
+    IntegerValue,
+    TextString,
+    FieldName,
+    ShortInteger,
+    ConstrainedEncoding,
+    DeltaSecondsValue,
+    NoValue,
+    TextValue,
+    DateValue,
+}
+
+// From wap-230-wsp table 38, the names of parameters which were deprecated in
+// later encoding versions are listed twice, the older codes come first since
+// they're the ones every phone understands
+pub(crate) const PARAMETER_CODES: [(u8, &str, ParameterValue); 29] = [
+    (0x00, "q", QValue),
+    (0x01, "charset", WellKnownCharset),
+    (0x02, "level", VersionValue),
+    (0x03, "type", IntegerValue),
+    (0x05, "name", TextString),
+    (0x06, "filename", TextString),
+    (0x07, "differences", FieldName),
+    (0x08, "padding", ShortInteger),
+    (0x09, "type", ConstrainedEncoding),
+    (0x0A, "start", TextString),
+    (0x0B, "start-info", TextString),
+    (0x0C, "comment", TextString),
+    (0x0D, "domain", TextString),
+    (0x0E, "max-age", DeltaSecondsValue),
+    (0x0F, "path", TextString),
+    (0x10, "secure", NoValue),
+    (0x11, "sec", ShortInteger),
+    (0x12, "mac", TextValue),
+    (0x13, "creation-date", DateValue),
+    (0x14, "modification-date", DateValue),
+    (0x15, "read-date", DateValue),
+    (0x16, "size", IntegerValue),
+    (0x17, "name", TextValue),
+    (0x18, "filename", TextValue),
+    (0x19, "start", TextValue),
+    (0x1A, "start-info", TextValue),
+    (0x1B, "comment", TextValue),
+    (0x1C, "domain", TextValue),
+    (0x1D, "path", TextValue),
+];
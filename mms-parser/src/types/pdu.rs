@@ -0,0 +1,896 @@
+use super::{
+    mms_header::{
+        Bool, CancelStatusField, ClassIdentifier, ContentClassField,
+        ContentType, ElementDescriptor, ExpiryField, FromField,
+        IndexedResponseStatus, IndexedString, LongUint, MMFlagsField,
+        MMStateField, MboxQuantityField, MessageTypeField, PreviouslySentBy,
+        PreviouslySentDate, PriorityField, ReadStatusField, ReplyChargingField,
+        ResponseStatusField, RetrievalModeField, RetrieveStatusField,
+        SenderVisibilityField, ShortUint, StatusField, StoreStatusField,
+        YesNoField,
+    },
+    multipart::PartBody,
+    Address, MmsHeader, MmsHeaderValue, VndWapMmsMessage,
+};
+use crate::ParseError;
+
+use std::{borrow::Cow, convert::TryFrom, time::SystemTime};
+
+/// The error for a required header which wasn't parsed, if it was in the
+/// message but couldn't be parsed, that error is returned instead of
+/// [`ParseError::MissingHeader`]
+fn missing_header(
+    message: &VndWapMmsMessage<'_>,
+    header: MmsHeader,
+) -> ParseError {
+    let message_type = crate::pdu::find_message_type(&message.raw_headers);
+
+    message
+        .raw_headers
+        .iter()
+        .filter(|(h, _)| *h == header)
+        .find_map(|(h, v)| {
+            crate::pdu::parse_header_value(message_type.as_ref(), h, v)
+                .err()
+                .map(|e| ParseError::from_nom(v, e))
+        })
+        .unwrap_or(ParseError::MissingHeader(header))
+}
+
+/// Generates a struct for each message type, and the `Pdu` enum which holds
+/// any one of them. Each is declared as `MessageType => Struct`, the struct
+/// drops the leading M so it doesn't share a name with the builder in
+/// [`encoder`](crate::encoder). Headers are listed as `field: Header as Type`, where
+/// `Type` must also be the name of the `MmsHeaderValue` variant holding it.
+/// X-Mms-Message-Type is implied by the struct. Repeated headers which are
+/// mandatory are also listed in `at_least_one_of`, which fails with the
+/// first of them when none are present. Messages with a body borrow
+/// it from the message they were parsed from, so they're declared as
+/// `Name<'a>`.
+// TODO: Header values are still owned, borrowing strings needs
+// MmsHeaderValue to borrow from the message as well
+macro_rules! pdu_types {
+    ($(
+        $(#[$meta:meta])*
+        $variant:ident => $name:ident $(<$lt:lifetime>)? {
+            required { $($req:ident: $req_header:ident as $req_type:ident),* $(,)* }
+            optional { $($opt:ident: $opt_header:ident as $opt_type:ident),* $(,)* }
+            repeated { $($rep:ident: $rep_header:ident as $rep_type:ident),* $(,)* }
+            $(at_least_one_of { $($one_header:ident),+ $(,)* })?
+            $(body: $body:ty)?
+        }
+    )+) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone)]
+            pub struct $name $(<$lt>)? {
+                $(pub $req: $req_type,)*
+                $(pub $opt: Option<$opt_type>,)*
+                $(pub $rep: Vec<$rep_type>,)*
+                $(pub body: $body,)?
+            }
+
+            impl<'a> TryFrom<VndWapMmsMessage<'a>> for $name $(<$lt>)? {
+                type Error = ParseError;
+
+                #[allow(unused_mut)]
+                fn try_from(mut message: VndWapMmsMessage<'a>) -> Result<Self, ParseError> {
+                    $(
+                        let headers = [$(MmsHeader::$one_header),+];
+                        if !headers.iter().any(|h| message.headers.contains_key(h)) {
+                            return Err(missing_header(&message, headers[0].clone()));
+                        }
+                    )?
+                    Ok(Self {
+                        $($req: match message.headers.remove(&MmsHeader::$req_header) {
+                            Some(MmsHeaderValue::$req_type(v)) => v,
+                            Some(_) => return Err(ParseError::UnexpectedValue(MmsHeader::$req_header)),
+                            None => return Err(missing_header(&message, MmsHeader::$req_header)),
+                        },)*
+                        $($opt: match message.headers.remove(&MmsHeader::$opt_header) {
+                            Some(MmsHeaderValue::$opt_type(v)) => Some(v),
+                            Some(_) => return Err(ParseError::UnexpectedValue(MmsHeader::$opt_header)),
+                            None => None,
+                        },)*
+                        $($rep: message
+                            .headers
+                            .remove_all(&MmsHeader::$rep_header)
+                            .map(|v| match v {
+                                MmsHeaderValue::$rep_type(v) => Ok(v),
+                                _ => Err(ParseError::UnexpectedValue(MmsHeader::$rep_header)),
+                            })
+                            .collect::<Result<_, _>>()?,)*
+                        $(body: {
+                            let body: $body = std::mem::take(&mut message.body);
+                            body
+                        },)?
+                    })
+                }
+            }
+        )+
+
+        /// A mms message, with it's headers checked against the ones
+        /// required and allowed for it's message type
+        #[derive(Debug, Clone)]
+        pub enum Pdu<'a> {
+            $($variant($name $(<$lt>)?),)+
+        }
+
+        impl Pdu<'_> {
+            pub fn message_type(&self) -> MessageTypeField {
+                match self {
+                    $(Self::$variant(_) => MessageTypeField::$variant,)+
+                }
+            }
+        }
+
+        impl<'a> TryFrom<VndWapMmsMessage<'a>> for Pdu<'a> {
+            type Error = ParseError;
+
+            fn try_from(message: VndWapMmsMessage<'a>) -> Result<Self, ParseError> {
+                let message_type = match message.x_mms_message_type() {
+                    Some(t) => t.clone(),
+                    None => return Err(ParseError::MissingHeader(MmsHeader::XMmsMessageType)),
+                };
+
+                match message_type {
+                    $(MessageTypeField::$variant => Ok(Self::$variant($name::try_from(message)?)),)+
+                    MessageTypeField::Unknown(_) => Err(ParseError::UnexpectedValue(MmsHeader::XMmsMessageType)),
+                }
+            }
+        }
+    };
+}
+
+// From OMA-TS-MMS_ENC-V1_3 section 6
+pdu_types! {
+    /// M-Send.req, sent to the MMSC to send a message
+    MSendReq => SendReq<'a> {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            from: From as FromField,
+            content_type: ContentType as ContentType,
+        }
+        optional {
+            date: Date as SystemTime,
+            subject: Subject as String,
+            x_mms_message_class: XMmsMessageClass as ClassIdentifier,
+            x_mms_expiry: XMmsExpiry as ExpiryField,
+            x_mms_delivery_time: XMmsDeliveryTime as ExpiryField,
+            x_mms_priority: XMmsPriority as PriorityField,
+            x_mms_sender_visibility: XMmsSenderVisibility as SenderVisibilityField,
+            x_mms_delivery_report: XMmsDeliveryReport as YesNoField,
+            x_mms_read_report: XMmsReadReport as YesNoField,
+            x_mms_store: XMmsStore as Bool,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            x_mms_reply_charging: XMmsReplyCharging as ReplyChargingField,
+            x_mms_reply_charging_deadline: XMmsReplyChargingDeadline as ExpiryField,
+            x_mms_reply_charging_size: XMmsReplyChargingSize as LongUint,
+            x_mms_reply_charging_id: XMmsReplyChargingID as String,
+            x_mms_content_class: XMmsContentClass as ContentClassField,
+            x_mms_drm_content: XMmsDRMContent as Bool,
+            x_mms_adaptation_allowed: XMmsAdaptationAllowed as YesNoField,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+        }
+        repeated {
+            to: To as Address,
+            cc: Cc as Address,
+            bcc: Bcc as Address,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+        }
+        at_least_one_of { To, Cc, Bcc }
+        body: Cow<'a, [u8]>
+    }
+
+    /// M-Send.conf, the MMSC's response to a M-Send.req
+    MSendConf => SendConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_response_status: XMmsResponseStatus as ResponseStatusField,
+        }
+        optional {
+            x_mms_response_text: XMmsResponseText as String,
+            message_id: MessageID as String,
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_store_status: XMmsStoreStatus as StoreStatusField,
+            x_mms_store_status_text: XMmsStoreStatusText as String,
+        }
+        repeated {}
+    }
+
+    /// M-Notification.ind, tells the client a message is waiting to be
+    /// fetched from `x_mms_content_location`
+    MNotificationInd => NotificationInd {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_message_class: XMmsMessageClass as ClassIdentifier,
+            x_mms_message_size: XMmsMessageSize as LongUint,
+            x_mms_expiry: XMmsExpiry as ExpiryField,
+            x_mms_content_location: XMmsContentLocation as String,
+        }
+        optional {
+            from: From as FromField,
+            subject: Subject as String,
+            x_mms_delivery_report: XMmsDeliveryReport as YesNoField,
+            x_mms_stored: XMmsStored as Bool,
+            x_mms_priority: XMmsPriority as PriorityField,
+            x_mms_reply_charging: XMmsReplyCharging as ReplyChargingField,
+            x_mms_reply_charging_deadline: XMmsReplyChargingDeadline as ExpiryField,
+            x_mms_reply_charging_size: XMmsReplyChargingSize as LongUint,
+            x_mms_reply_charging_id: XMmsReplyChargingID as String,
+            x_mms_distribution_indicator: XMmsDistributionIndicator as Bool,
+            x_mms_recommended_retrieval_mode: XMmsRecommendedRetrievalMode as RetrievalModeField,
+            x_mms_recommended_retrieval_mode_text: XMmsRecommendedRetrievalModeText as String,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+            x_mms_content_class: XMmsContentClass as ContentClassField,
+            x_mms_drm_content: XMmsDRMContent as Bool,
+            x_mms_replace_id: XMmsReplaceID as String,
+        }
+        repeated {
+            x_mms_element_descriptor: XMmsElementDescriptor as ElementDescriptor,
+        }
+    }
+
+    /// M-NotifyResp.ind, the client's response to a M-Notification.ind
+    MNotifyrespInd => NotifyrespInd {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_status: XMmsStatus as StatusField,
+        }
+        optional {
+            x_mms_report_allowed: XMmsReportAllowed as Bool,
+        }
+        repeated {}
+    }
+
+    /// M-Retrieve.conf, a message fetched from the MMSC
+    MRetrieveConf => RetrieveConf<'a> {
+        required {
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            date: Date as SystemTime,
+            content_type: ContentType as ContentType,
+        }
+        optional {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            message_id: MessageID as String,
+            from: From as FromField,
+            subject: Subject as String,
+            x_mms_message_class: XMmsMessageClass as ClassIdentifier,
+            x_mms_priority: XMmsPriority as PriorityField,
+            x_mms_delivery_report: XMmsDeliveryReport as YesNoField,
+            x_mms_read_report: XMmsReadReport as YesNoField,
+            x_mms_reply_charging: XMmsReplyCharging as ReplyChargingField,
+            x_mms_reply_charging_deadline: XMmsReplyChargingDeadline as ExpiryField,
+            x_mms_reply_charging_size: XMmsReplyChargingSize as LongUint,
+            x_mms_reply_charging_id: XMmsReplyChargingID as String,
+            x_mms_retrieve_status: XMmsRetrieveStatus as RetrieveStatusField,
+            x_mms_retrieve_text: XMmsRetrieveText as String,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            x_mms_distribution_indicator: XMmsDistributionIndicator as Bool,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+            x_mms_content_class: XMmsContentClass as ContentClassField,
+            x_mms_drm_content: XMmsDRMContent as Bool,
+            x_mms_replace_id: XMmsReplaceID as String,
+        }
+        repeated {
+            to: To as Address,
+            cc: Cc as Address,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+            x_mms_previously_sent_by: XMmsPreviouslySentBy as PreviouslySentBy,
+            x_mms_previously_sent_date: XMmsPreviouslySentDate as PreviouslySentDate,
+        }
+        body: Cow<'a, [u8]>
+    }
+
+    /// M-Acknowledge.ind, sent by the client after fetching a message
+    MAcknowledgeInd => AcknowledgeInd {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {
+            x_mms_report_allowed: XMmsReportAllowed as Bool,
+        }
+        repeated {}
+    }
+
+    /// M-Delivery.ind, a delivery report for a message sent by the client
+    MDeliveryInd => DeliveryInd {
+        required {
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            message_id: MessageID as String,
+            date: Date as SystemTime,
+            x_mms_status: XMmsStatus as StatusField,
+        }
+        optional {
+            x_mms_status_text: XMmsStatusText as String,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+        }
+        repeated {
+            to: To as Address,
+        }
+        at_least_one_of { To }
+    }
+
+    /// M-Read-Rec.ind, a read report sent by the recipient of a message
+    MReadRecInd => ReadRecInd {
+        required {
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            message_id: MessageID as String,
+            from: From as FromField,
+            x_mms_read_status: XMmsReadStatus as ReadStatusField,
+        }
+        optional {
+            date: Date as SystemTime,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+        }
+        repeated {
+            to: To as Address,
+        }
+        at_least_one_of { To }
+    }
+
+    /// M-Read-Orig.ind, a read report forwarded to the sender by the MMSC
+    MReadOrigInd => ReadOrigInd {
+        required {
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            message_id: MessageID as String,
+            from: From as FromField,
+            date: Date as SystemTime,
+            x_mms_read_status: XMmsReadStatus as ReadStatusField,
+        }
+        optional {
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+        }
+        repeated {
+            to: To as Address,
+        }
+    }
+
+    /// M-Forward.req, asks the MMSC to forward a message it has stored
+    MForwardReq => ForwardReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            from: From as FromField,
+            x_mms_content_location: XMmsContentLocation as String,
+        }
+        optional {
+            date: Date as SystemTime,
+            x_mms_expiry: XMmsExpiry as ExpiryField,
+            x_mms_delivery_time: XMmsDeliveryTime as ExpiryField,
+            x_mms_report_allowed: XMmsReportAllowed as Bool,
+            x_mms_delivery_report: XMmsDeliveryReport as YesNoField,
+            x_mms_read_report: XMmsReadReport as YesNoField,
+            x_mms_store: XMmsStore as Bool,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+        }
+        repeated {
+            to: To as Address,
+            cc: Cc as Address,
+            bcc: Bcc as Address,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+        }
+    }
+
+    /// M-Forward.conf, the MMSC's response to a M-Forward.req
+    MForwardConf => ForwardConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_response_status: XMmsResponseStatus as ResponseStatusField,
+        }
+        optional {
+            x_mms_response_text: XMmsResponseText as String,
+            message_id: MessageID as String,
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_store_status: XMmsStoreStatus as StoreStatusField,
+            x_mms_store_status_text: XMmsStoreStatusText as String,
+        }
+        repeated {}
+    }
+
+    /// M-Mbox-Store.req, asks the MMSC to store a message in the MMBox
+    MMboxStoreReq => MboxStoreReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_content_location: XMmsContentLocation as String,
+        }
+        optional {
+            x_mms_mm_state: XMmsMMState as MMStateField,
+        }
+        repeated {
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+        }
+    }
+
+    /// M-Mbox-Store.conf, the MMSC's response to a M-Mbox-Store.req
+    MMboxStoreConf => MboxStoreConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_store_status: XMmsStoreStatus as StoreStatusField,
+        }
+        optional {
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_store_status_text: XMmsStoreStatusText as String,
+        }
+        repeated {}
+    }
+
+    /// M-Mbox-View.req, asks the MMSC for information about the MMBox, and
+    /// the messages in it
+    MMboxViewReq => MboxViewReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {
+            x_mms_start: XMmsStart as LongUint,
+            x_mms_limit: XMmsLimit as LongUint,
+            x_mms_totals: XMmsTotals as Bool,
+            x_mms_quotas: XMmsQuotas as Bool,
+        }
+        repeated {
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+            x_mms_attributes: XMmsAttributes as MmsHeader,
+        }
+    }
+
+    /// M-Mbox-View.conf, the MMSC's response to a M-Mbox-View.req, the body
+    /// contains a M-Mbox-Descr for each message
+    MMboxViewConf => MboxViewConf<'a> {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_response_status: XMmsResponseStatus as ResponseStatusField,
+        }
+        optional {
+            x_mms_response_text: XMmsResponseText as String,
+            x_mms_start: XMmsStart as LongUint,
+            x_mms_limit: XMmsLimit as LongUint,
+            x_mms_message_count: XMmsMessageCount as LongUint,
+            content_type: ContentType as ContentType,
+        }
+        repeated {
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+            x_mms_attributes: XMmsAttributes as MmsHeader,
+            x_mms_mbox_totals: XMmsMboxTotals as MboxQuantityField,
+            x_mms_mbox_quotas: XMmsMboxQuotas as MboxQuantityField,
+        }
+        body: Cow<'a, [u8]>
+    }
+
+    /// M-Mbox-Upload.req, stores the message in the body in the MMBox
+    MMboxUploadReq => MboxUploadReq<'a> {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            content_type: ContentType as ContentType,
+        }
+        optional {
+            x_mms_mm_state: XMmsMMState as MMStateField,
+        }
+        repeated {
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+        }
+        body: Cow<'a, [u8]>
+    }
+
+    /// M-Mbox-Upload.conf, the MMSC's response to a M-Mbox-Upload.req
+    MMboxUploadConf => MboxUploadConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_store_status: XMmsStoreStatus as StoreStatusField,
+        }
+        optional {
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_store_status_text: XMmsStoreStatusText as String,
+        }
+        repeated {}
+    }
+
+    /// M-Mbox-Delete.req, deletes messages from the MMBox
+    MMboxDeleteReq => MboxDeleteReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {}
+        repeated {
+            x_mms_content_location: XMmsContentLocation as String,
+        }
+    }
+
+    /// M-Mbox-Delete.conf, the MMSC's response to a M-Mbox-Delete.req
+    MMboxDeleteConf => MboxDeleteConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {}
+        repeated {
+            x_mms_response_status: XMmsResponseStatus as IndexedResponseStatus,
+            x_mms_response_text: XMmsResponseText as IndexedString,
+            x_mms_content_location: XMmsContentLocation as IndexedString,
+        }
+    }
+
+    /// M-Mbox-Descr, describes a single message stored in the MMBox
+    MMboxDescr => MboxDescr<'a> {
+        required {
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            message_id: MessageID as String,
+            x_mms_content_location: XMmsContentLocation as String,
+            x_mms_mm_state: XMmsMMState as MMStateField,
+            date: Date as SystemTime,
+            from: From as FromField,
+            x_mms_message_class: XMmsMessageClass as ClassIdentifier,
+            subject: Subject as String,
+            x_mms_priority: XMmsPriority as PriorityField,
+            x_mms_delivery_time: XMmsDeliveryTime as ExpiryField,
+            x_mms_expiry: XMmsExpiry as ExpiryField,
+            x_mms_delivery_report: XMmsDeliveryReport as YesNoField,
+            x_mms_read_report: XMmsReadReport as YesNoField,
+            x_mms_message_size: XMmsMessageSize as LongUint,
+            x_mms_reply_charging: XMmsReplyCharging as ReplyChargingField,
+            x_mms_reply_charging_deadline: XMmsReplyChargingDeadline as ExpiryField,
+            x_mms_reply_charging_size: XMmsReplyChargingSize as LongUint,
+            x_mms_reply_charging_id: XMmsReplyChargingID as String,
+            x_mms_applic_id: XMmsApplicID as String,
+            x_mms_reply_applic_id: XMmsReplyApplicID as String,
+            x_mms_aux_applic_info: XMmsAuxApplicInfo as String,
+            content_type: ContentType as ContentType,
+        }
+        repeated {
+            to: To as Address,
+            cc: Cc as Address,
+            bcc: Bcc as Address,
+            x_mms_mm_flags: XMmsMMFlags as MMFlagsField,
+            x_mms_previously_sent_by: XMmsPreviouslySentBy as PreviouslySentBy,
+            x_mms_previously_sent_date: XMmsPreviouslySentDate as PreviouslySentDate,
+        }
+        body: Cow<'a, [u8]>
+    }
+
+    /// M-Delete.req, deletes messages which are waiting on the MMSC
+    MDeleteReq => DeleteReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {}
+        repeated {
+            x_mms_content_location: XMmsContentLocation as String,
+        }
+    }
+
+    /// M-Delete.conf, the MMSC's response to a M-Delete.req
+    MDeleteConf => DeleteConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+        }
+        optional {}
+        repeated {
+            x_mms_response_status: XMmsResponseStatus as IndexedResponseStatus,
+            x_mms_response_text: XMmsResponseText as IndexedString,
+            x_mms_content_location: XMmsContentLocation as IndexedString,
+        }
+    }
+
+    /// M-Cancel.req, tells the client to stop fetching a message
+    MCancelReq => CancelReq {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_cancel_id: XMmsCancelID as String,
+        }
+        optional {}
+        repeated {}
+    }
+
+    /// M-Cancel.conf, the client's response to a M-Cancel.req
+    MCancelConf => CancelConf {
+        required {
+            x_mms_transaction_id: XMmsTransactionId as String,
+            x_mms_mms_version: XMmsMMSVersion as ShortUint,
+            x_mms_cancel_status: XMmsCancelStatus as CancelStatusField,
+        }
+        optional {}
+        repeated {}
+    }
+}
+
+impl MboxViewConf<'_> {
+    /// Parse the M-Mbox-Descr for each message in the body
+    pub fn descriptions(&self) -> Result<Vec<MboxDescr<'_>>, ParseError> {
+        if self.body.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        crate::parse_multipart_body(&self.body)?
+            .parts
+            .into_iter()
+            .filter_map(|part| match part.body {
+                PartBody::Data(data) => Some(data),
+                PartBody::MultiPart(_) => None,
+            })
+            .map(|data| {
+                let message = match data {
+                    Cow::Borrowed(data) => crate::parse_mms_pdu(data)?,
+                    Cow::Owned(data) => {
+                        crate::parse_mms_pdu(&data)?.into_owned()
+                    }
+                };
+                MboxDescr::try_from(message)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_mms_pdu;
+    use crate::types::mms_header::Indexed;
+
+    /// Convert a parsed message, expecting it to be a `$variant`
+    macro_rules! expect_pdu {
+        ($variant:ident, $message:expr) => {
+            match Pdu::try_from($message).unwrap() {
+                Pdu::$variant(p) => p,
+                p => panic!("Unexpected pdu {:?}", p),
+            }
+        };
+    }
+
+    #[test]
+    fn notification_ind() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x82\x98abc\0\x8D\x92\x8A\x80\x8E\x02\x10\x00\
+            \x88\x05\x81\x03\x01\x51\x80\x83http://mmsc/abc\0",
+        )
+        .unwrap();
+
+        let n = expect_pdu!(MNotificationInd, pdu);
+        assert_eq!(n.x_mms_transaction_id, "abc");
+        assert_eq!(n.x_mms_message_size, 0x1000);
+        assert_eq!(n.x_mms_content_location, "http://mmsc/abc");
+        assert!(n.from.is_none());
+    }
+
+    #[test]
+    fn notification_ind_without_content_location() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x82\x98abc\0\x8D\x92\x8A\x80\x8E\x02\x10\x00\
+            \x88\x05\x81\x03\x01\x51\x80",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::MissingHeader(MmsHeader::XMmsContentLocation)
+        );
+    }
+
+    #[test]
+    fn invalid_required_header_is_reported() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x82\x98abc\0\x8D\x92\x8A\x80\x8E\x02\x10\x00\
+            \x88\x05\x85\x03\x01\x51\x80\x83http://mmsc/abc\0",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::Invalid {
+                header: Some(MmsHeader::XMmsExpiry),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn missing_message_type() {
+        let pdu = parse_mms_pdu(b"\x98abc\0").unwrap();
+
+        assert_eq!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::MissingHeader(MmsHeader::XMmsMessageType)
+        );
+    }
+
+    #[test]
+    fn delivery_ind() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let pdu = parse_mms_pdu(
+            b"\x8C\x86\x8D\x92\x8Babc\0\x97+1/TYPE=PLMN\0\
+            \x85\x04\x5F\x5E\x10\x00\x95\x81",
+        )
+        .unwrap();
+
+        let d = expect_pdu!(MDeliveryInd, pdu);
+        assert_eq!(d.message_id, "abc");
+        assert_eq!(d.to, vec![Address::Plmn("+1".to_string())]);
+        assert_eq!(d.date, UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        assert_eq!(d.x_mms_status, StatusField::Retrieved);
+    }
+
+    #[test]
+    fn repeated_to() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x86\x8D\x92\x8Babc\0\x85\x01\x01\x95\x80\
+            \x97+1/TYPE=PLMN\0\x97+2/TYPE=PLMN\0",
+        )
+        .unwrap();
+        let to = vec![
+            Address::Plmn("+1".to_string()),
+            Address::Plmn("+2".to_string()),
+        ];
+        assert_eq!(pdu.to(), to);
+
+        let d = expect_pdu!(MDeliveryInd, pdu);
+        assert_eq!(d.to, to);
+    }
+
+    #[test]
+    fn missing_to() {
+        // M-Delivery.ind
+        let pdu =
+            parse_mms_pdu(b"\x8C\x86\x8D\x92\x8Babc\0\x85\x01\x01\x95\x80")
+                .unwrap();
+        assert_eq!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::MissingHeader(MmsHeader::To)
+        );
+
+        // M-Read-Rec.ind
+        let pdu = parse_mms_pdu(
+            b"\x8C\x87\x8D\x92\x8Babc\0\x89\x0E\x80+2/TYPE=PLMN\0\x9B\x80",
+        )
+        .unwrap();
+        assert_eq!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::MissingHeader(MmsHeader::To)
+        );
+    }
+
+    #[test]
+    fn send_req_needs_a_recipient() {
+        let pdu =
+            parse_mms_pdu(b"\x8C\x80\x98t\0\x8D\x92\x89\x01\x81\x84\x83hi")
+                .unwrap();
+        assert_eq!(
+            Pdu::try_from(pdu).unwrap_err(),
+            ParseError::MissingHeader(MmsHeader::To)
+        );
+
+        let pdu = parse_mms_pdu(
+            b"\x8C\x80\x98t\0\x8D\x92\x89\x01\x81\x81+1/TYPE=PLMN\0\x84\x83hi",
+        )
+        .unwrap();
+        let s = expect_pdu!(MSendReq, pdu);
+        assert!(s.to.is_empty());
+        assert_eq!(s.bcc, vec![Address::Plmn("+1".to_string())]);
+    }
+
+    #[test]
+    fn read_orig_ind() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x88\x8D\x92\x8Babc\0\x97+1/TYPE=PLMN\0\
+            \x89\x0E\x80+2/TYPE=PLMN\0\x85\x01\x01\x9B\x80",
+        )
+        .unwrap();
+
+        let r = expect_pdu!(MReadOrigInd, pdu);
+        assert_eq!(r.message_id, "abc");
+        assert_eq!(r.from, FromField::Address(Address::Plmn("+2".to_string())));
+        assert_eq!(r.x_mms_read_status, ReadStatusField::Read);
+    }
+
+    #[test]
+    fn forward_conf() {
+        let pdu =
+            parse_mms_pdu(b"\x8C\x8A\x98t\0\x8D\x92\x92\xE1\x8Bid\0").unwrap();
+
+        let f = expect_pdu!(MForwardConf, pdu);
+        assert_eq!(
+            f.x_mms_response_status,
+            ResponseStatusField::ErrorPermanentServiceDenied
+        );
+        assert!(f.x_mms_response_status.is_permanent());
+        assert_eq!(f.message_id.unwrap(), "id");
+    }
+
+    #[test]
+    fn mbox_delete_conf_is_indexed() {
+        let pdu = parse_mms_pdu(
+            b"\x8C\x92\x98t\0\x8D\x92\x92\x02\x80\x80\x92\x02\x81\xE1\
+            \x93\x05\x81bad\0\x83\x04\x81ab\0",
+        )
+        .unwrap();
+
+        let d = expect_pdu!(MMboxDeleteConf, pdu);
+        assert_eq!(
+            d.x_mms_response_status,
+            [
+                Indexed {
+                    index: 0,
+                    value: ResponseStatusField::Ok
+                },
+                Indexed {
+                    index: 1,
+                    value: ResponseStatusField::ErrorPermanentServiceDenied
+                },
+            ]
+        );
+        assert_eq!(
+            d.x_mms_response_text,
+            [Indexed {
+                index: 1,
+                value: "bad".to_string()
+            }]
+        );
+        assert_eq!(
+            d.x_mms_content_location,
+            [Indexed {
+                index: 1,
+                value: "ab".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn mbox_view_conf_descriptions() {
+        let descr = b"\x8C\x93\x8D\x92\x83http://mmsc/a\0\x97+1/TYPE=PLMN\0";
+        // One part with a Content-Type of application/vnd.wap.mms-message
+        let mut pdu =
+            b"\x8C\x8E\x98t\0\x8D\x92\x92\x80\x84\xA3\x01\x01".to_vec();
+        pdu.push(descr.len() as u8);
+        pdu.push(0xBE);
+        pdu.extend_from_slice(descr);
+
+        let v = expect_pdu!(MMboxViewConf, parse_mms_pdu(&pdu).unwrap());
+        assert_eq!(v.x_mms_response_status, ResponseStatusField::Ok);
+        // The body isn't copied out of the message
+        assert!(matches!(v.body, Cow::Borrowed(_)));
+
+        let descriptions = v.descriptions().unwrap();
+        assert_eq!(descriptions.len(), 1);
+        assert_eq!(
+            descriptions[0].x_mms_content_location.as_deref(),
+            Some("http://mmsc/a")
+        );
+        assert_eq!(descriptions[0].to, [Address::Plmn("+1".to_string())]);
+    }
+}
@@ -1,13 +1,22 @@
 use mms_parser::{
-    encoder::MSendReq,
-    parse_mms_pdu, parse_wap_push,
-    types::{message_header::MessageHeader, mms_header::FromField},
+    encoder::{MForwardReq, MNotifyRespInd, MSendReq},
+    parse_mms_pdu, parse_wap_push, read_mms_pdu_headers,
+    types::{
+        message_header::MessageHeader,
+        mms_header::{
+            FromField, MessageTypeField, ResponseStatusField, StatusField,
+        },
+        multipart::is_multipart,
+        Address, Pdu, VndWapMmsMessage,
+    },
+    Charset, MultiPartReader,
 };
 
 use std::{
+    convert::TryFrom,
     fs::{DirBuilder, File},
-    io::{prelude::*, Read},
-    path::PathBuf,
+    io::{self, prelude::*, BufReader, Read},
+    path::{Path, PathBuf},
 };
 
 #[macro_use]
@@ -31,27 +40,32 @@ enum Command {
     Cat(CatArgs),
     Decode(DecodeArgs),
     Encode(EncodeArgs),
+    Forward(ForwardArgs),
 }
 
 #[derive(StructOpt, Debug)]
 struct EncodeArgs {
     /// Your phone number
     #[structopt(short, long)]
-    from: Option<u64>,
-    /// The number of the recipient of this message
-    #[structopt(
-        short,
-        long,
-        required_unless("unchecked-to"),
-        conflicts_with("unchecked-to")
-    )]
-    to: Option<u64>,
-    /// Used to send a message to something other than a mobile phone number
-    #[structopt(long)]
-    unchecked_to: Option<String>,
+    from: Option<Address>,
+    /// A recipient of this message, either a phone number, an email
+    /// address, or any address with a /TYPE= suffix, for example
+    /// 192.0.2.1/TYPE=IPv4. Can be specified multiple times
+    #[structopt(short, long, required = true, number_of_values = 1)]
+    to: Vec<Address>,
+    /// A recipient to send a carbon copy to, can be specified multiple times
+    #[structopt(long, number_of_values = 1)]
+    cc: Vec<Address>,
+    /// A recipient to send a blind carbon copy to, can be specified multiple
+    /// times
+    #[structopt(long, number_of_values = 1)]
+    bcc: Vec<Address>,
     /// Subject of the message
     #[structopt(long)]
     subject: Option<String>,
+    /// Charset to encode the subject in, for example UTF-8 or UTF-16
+    #[structopt(long)]
+    charset: Option<Charset>,
     /// Files to send. If specified multiple times, then send a multipart message where the first
     /// file specified is a smil display setction
     #[structopt(
@@ -68,23 +82,9 @@ struct EncodeArgs {
 }
 
 impl EncodeArgs {
-    fn to(&self) -> Result<String> {
-        let to = if let Some(to) = self.to {
-            format!("+{}/TYPE=PLMN", to)
-        } else if let Some(ref to) = self.unchecked_to {
-            to.clone()
-        } else {
-            unreachable!(
-                "Either args.to, or args.unchecked to must have a value, \
-                this is a bug, please try to reproduce it and file a bug report"
-                );
-        };
-        Ok(to)
-    }
-
     fn from(&self) -> FromField {
-        if let Some(from) = self.from {
-            FromField::Address(format!("+{}/TYPE=PLMN", from))
+        if let Some(from) = &self.from {
+            FromField::Address(from.clone())
         } else {
             FromField::InsertAddress
         }
@@ -126,6 +126,43 @@ struct FetchArgs {
     /// A subdirectory with the id of the message will be created
     #[structopt(name = "Output", parse(from_os_str))]
     output: PathBuf,
+    /// Url of your carrier's MMSC, used to tell it the message was received.
+    /// Without it the MMSC may send the notification again
+    #[structopt(long)]
+    mmsc: Option<String>,
+}
+
+/// Forward a message which is waiting on the MMSC without downloading it
+#[derive(StructOpt, Debug)]
+struct ForwardArgs {
+    #[structopt(flatten)]
+    netargs: NetArgs,
+    /// A file containing the notification of the message to forward
+    #[structopt(name = "Notification", parse(from_os_str))]
+    file: PathBuf,
+    /// Url of your carrier's MMSC
+    #[structopt(long)]
+    mmsc: String,
+    /// Your phone number
+    #[structopt(short, long)]
+    from: Option<Address>,
+    /// A recipient to forward the message to, can be specified multiple
+    /// times
+    #[structopt(short, long, required = true, number_of_values = 1)]
+    to: Vec<Address>,
+    /// A recipient to send a carbon copy to, can be specified multiple times
+    #[structopt(long, number_of_values = 1)]
+    cc: Vec<Address>,
+    /// A recipient to send a blind carbon copy to, can be specified multiple
+    /// times
+    #[structopt(long, number_of_values = 1)]
+    bcc: Vec<Address>,
+    /// Ask for a delivery report
+    #[structopt(long)]
+    delivery_report: bool,
+    /// Ask for a read report
+    #[structopt(long)]
+    read_report: bool,
 }
 
 #[derive(StructOpt, Debug)]
@@ -149,17 +186,18 @@ fn main() -> anyhow::Result<()> {
 
     match args.cmd {
         Command::Fetch(args) => fetch(args)?,
-        Command::Cat(args) => cat(args),
+        Command::Cat(args) => cat(args)?,
         Command::Decode(args) => command_decode(args)?,
         Command::Encode(args) => encode_to_file(args)?,
+        Command::Forward(args) => forward(args)?,
     }
 
     Ok(())
 }
 
-fn cat(args: CatArgs) {
+fn cat(args: CatArgs) -> Result<()> {
     // pager::Pager::with_default_pager("less").setup();
-    let data = read_file(&args.file).expect("Could not read data file");
+    let data = read_file(&args.file).context("Could not read data file")?;
 
     // X-Mms-Message-Type must always be the first header of any mms pdu we can
     // use this to tell weather the provided data is a mms pdu, or a wap pdu
@@ -167,15 +205,15 @@ fn cat(args: CatArgs) {
     if data[0] == 0x8C {
         println!("Type: Mms Data");
 
-        let (_remainder, parsed) =
-            parse_mms_pdu(&*data).expect("Unable to parse provided data file");
+        let parsed = parse_mms_pdu(&*data)
+            .context("Unable to parse provided data file")?;
 
         println!("Headers: {:#?}", parsed.headers);
 
         if parsed.body.len() > 0 {
             if parsed.has_multipart_body() {
-                let body =
-                    mms_parser::parse_multipart_body(&*parsed.body).unwrap().1;
+                let body = mms_parser::parse_multipart_body(&*parsed.body)
+                    .context("Unable to parse message body")?;
                 println!("Body: {:#?}", body);
             } else {
                 let body = String::from_utf8_lossy(&parsed.body);
@@ -185,27 +223,36 @@ fn cat(args: CatArgs) {
     } else {
         println!("Type: WAP Data");
 
-        let (_, parsed) = parse_wap_push(&data).unwrap();
+        let parsed =
+            parse_wap_push(&data).context("Unable to parse wap push")?;
         println!("Wap Push Headers: {:#?}", parsed);
 
-        let body = parsed.parse_body().expect("Unable to parse wap push body");
+        let body = parsed
+            .parse_body()
+            .context("Unable to parse wap push body")?;
         println!("Wap Push Body: {:#?}", body);
     }
+
+    Ok(())
 }
 
 fn command_decode(args: DecodeArgs) -> anyhow::Result<()> {
-    let data = read_file(&args.file).expect("Could not read data file");
+    let file = File::open(&args.file)
+        .with_context(|| anyhow!("Could not read data file {:?}", args.file))?;
+    let mut reader = BufReader::new(file);
 
-    if data[0] != 0x8C {
+    // X-Mms-Message-Type is always the first header of a mms pdu
+    let first = reader.fill_buf().context("Could not read data file")?;
+    if first.first() != Some(&0x8C) {
         bail!("Unknown data type, please provide a mms pdu");
     }
 
-    let (_remainder, message) =
-        parse_mms_pdu(&*data).expect("Unable to parse provided data file");
+    let message = read_mms_pdu_headers(&mut reader)
+        .context("Unable to parse provided data file")?;
 
     println!("Headers: {:#?}", message.headers);
 
-    if message.body.len() == 0 {
+    if message.content_type().is_none() {
         println!(
             "WARNING: data file contained no body part, no new data was saved"
         );
@@ -227,45 +274,19 @@ fn command_decode(args: DecodeArgs) -> anyhow::Result<()> {
 
     DirBuilder::new().create(&out)?;
 
-    save_body(&message, out)
+    save_body(&message, reader, out)
 }
 
-fn save_body(
-    message: &mms_parser::types::VndWapMmsMessage,
+/// Save the body of `message`, which is read from `body`, to the directory
+/// `out`. Multipart bodies are saved one part at a time
+fn save_body<R: Read>(
+    message: &VndWapMmsMessage<'_>,
+    mut body: R,
     mut out: PathBuf,
 ) -> anyhow::Result<()> {
-    use MessageHeader::ContentLocation;
     if message.has_multipart_body() {
-        let body = mms_parser::parse_multipart_body(&message.body)
-            .map_err(|e| {
-                // TODO: This is the correct solution here, however it's blocked by
-                // https://github.com/Geal/nom/issues/1254
-                // e.to_owned();
-                anyhow!(e.to_string())
-            })?
-            .1;
-        let mut error = Ok(());
-
-        for item in body {
-            let content_location = item
-                .headers
-                .iter()
-                .find_map(|h| {
-                    if let ContentLocation(h) = h {
-                        Some(h.clone())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(Uuid::new_v4().to_string());
-
-            let mut file_path = out.clone();
-            file_path.push(content_location);
-
-            error = error.or(write_file(&file_path, &*item.body))
-        }
-
-        error?;
+        let body: &mut dyn Read = &mut body;
+        save_parts(MultiPartReader::new(body), &out)
     } else {
         let content_type = if let Some(c) = message.content_type() {
             c
@@ -276,10 +297,61 @@ fn save_body(
 
         out.push("Unnamed-1");
         out.set_extension(mime_db::extension(content_type).unwrap_or("bin"));
-        write_file(&out, &*message.body)?;
-    };
+        copy_to_file(&out, &mut body)
+    }
+}
 
-    Ok(())
+/// Save every part which isn't a multipart body itself to `out`, parts which
+/// can't be saved don't stop the rest from being saved
+fn save_parts(
+    mut parts: MultiPartReader<&mut dyn Read>,
+    out: &Path,
+) -> anyhow::Result<()> {
+    use MessageHeader::{ContentDisposition, ContentLocation};
+    let mut error = Ok(());
+
+    while let Some(mut part) =
+        parts.next_part().context("Unable to parse message body")?
+    {
+        if is_multipart(&part.content_type) {
+            let part: &mut dyn Read = &mut part;
+            error = error.and(save_parts(MultiPartReader::new(part), out));
+            continue;
+        }
+
+        let name = part
+            .headers
+            .iter()
+            .find_map(|h| {
+                if let ContentLocation(h) = h {
+                    Some(h.clone())
+                } else {
+                    None
+                }
+            })
+            .or_else(|| {
+                part.headers.iter().find_map(|h| match h {
+                    ContentDisposition(d) => {
+                        d.filename().map(|f| f.to_string())
+                    }
+                    _ => None,
+                })
+            });
+        // The names come from the message, so only their last component is
+        // used, otherwise they could point outside of `out`
+        let file_name = name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .map(|name| name.to_os_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string().into());
+
+        let mut file_path = out.to_path_buf();
+        file_path.push(file_name);
+
+        error = error.and(copy_to_file(&file_path, &mut part));
+    }
+
+    error
 }
 
 fn encode_to_file(args: EncodeArgs) -> Result<()> {
@@ -290,15 +362,28 @@ fn encode_to_file(args: EncodeArgs) -> Result<()> {
     }
 
     let mut message = MSendReq::new();
-    message.to(args.to()?);
+    for to in &args.to {
+        message.to(to.clone());
+    }
+    for cc in &args.cc {
+        message.cc(cc.clone());
+    }
+    for bcc in &args.bcc {
+        message.bcc(bcc.clone());
+    }
     message.from(args.from());
     args.subject.map(|subject| message.subject(subject));
+    if let Some(charset) = args.charset {
+        message.charset(charset);
+    }
 
     for file in args.files {
-        message.body_file(file);
+        message
+            .body_file(&file)
+            .with_context(|| format!("Unable to add {:?}", file))?;
     }
 
-    write_file(&args.output, &*message.encode())
+    write_file(&args.output, &*message.encode()?)
         .context("Unable to save message to output")?;
     Ok(())
 }
@@ -326,19 +411,56 @@ fn fetch(args: FetchArgs) -> Result<()> {
         bail!("Output must be a directory that exists");
     }
 
-    let data = read_file(&args.file).context("Notification File")?;
+    let message = read_notification(&args.file).context("Notification File")?;
+    // Only the transaction id and content location are needed to fetch the
+    // message, so it's still fetched if other headers are invalid
+    let fallback = match (
+        message.x_mms_message_type(),
+        message.x_mms_transaction_id(),
+        message.x_mms_content_location().into_iter().next(),
+    ) {
+        (
+            Some(MessageTypeField::MNotificationInd),
+            Some(id),
+            Some(location),
+        ) => Some((id.clone(), location)),
+        _ => None,
+    };
 
-    let (_, parsed) = parse_wap_push(&data).unwrap();
-    let body = parsed
-        .parse_body()
-        .ok_or(anyhow!("Failed to parse message notification"))?;
+    let (transaction_id, content_location) = match Pdu::try_from(message) {
+        Ok(Pdu::MNotificationInd(n)) => {
+            (n.x_mms_transaction_id, n.x_mms_content_location)
+        }
+        // Reports are sent the same way as notifications, but there's
+        // nothing to fetch
+        Ok(Pdu::MDeliveryInd(d)) => {
+            println!(
+                "Delivery report for message {}: {}",
+                d.message_id, d.x_mms_status
+            );
+            return Ok(());
+        }
+        Ok(Pdu::MReadOrigInd(r)) => {
+            println!(
+                "Read report for message {}: {}",
+                r.message_id, r.x_mms_read_status
+            );
+            return Ok(());
+        }
+        Ok(p) => {
+            bail!("Expected a message notification, got {}", p.message_type())
+        }
+        Err(e) => match fallback {
+            Some(fallback) => {
+                println!("WARNING: Invalid message notification: {}", e);
+                fallback
+            }
+            None => return Err(e).context("Invalid message notification"),
+        },
+    };
 
     let mut output = args.output.clone();
-    output.push(body.x_mms_transaction_id().ok_or(
-        anyhow!("Invalid message notification").context(
-            "Message notifications must have a X-Mms-Transaction-ID field",
-        ),
-    )?);
+    output.push(&transaction_id);
 
     if output.exists() {
         bail!(
@@ -350,66 +472,196 @@ fn fetch(args: FetchArgs) -> Result<()> {
 
     DirBuilder::new().create(&output)?;
 
-    let message_url = body.x_mms_content_location().ok_or(
-        anyhow!("Invalid MMs notification. Message notification must contain a content-location field.",
-        ),
-    )?;
+    let message_url = &content_location;
+
+    let client = http_client(args.netargs)?;
 
+    let mut response = client.get(message_url)?;
+    let status = response.status();
+
+    // Everything read from the server is also saved as is
+    let mut raw_path = output.clone();
+    raw_path.push("m-retrieve-conf.bin");
+    let raw = File::create(&raw_path)
+        .context("Could not save response from server")?;
+    let mut body = BufReader::new(TeeReader {
+        reader: response.body_mut(),
+        copy: raw,
+    });
+
+    if !status.is_success() {
+        io::copy(&mut body, &mut io::sink())
+            .context("Could not save response from server")?;
+        drop(body);
+        bail!(
+            "Received error while trying to fetch message: {:#?}",
+            response
+        );
+    }
+
+    let message = read_mms_pdu_headers(&mut body)
+        .context("Could not parse response from server")?;
+
+    println!("Message Response Headers: {:#?}", message.headers);
+
+    save_body(&message, &mut body, output)?;
+    io::copy(&mut body, &mut io::sink())
+        .context("Could not save response from server")?;
+    drop(body);
+
+    let mmsc = if let Some(mmsc) = &args.mmsc {
+        mmsc
+    } else {
+        println!(
+            "No MMSC was given, so it wasn't told the message was received"
+        );
+        return Ok(());
+    };
+
+    // The message is fetched as soon as the notification is read, which is
+    // immediate retrieval, so the notification is answered with a
+    // M-NotifyResp.ind. M-Acknowledge.ind is only for deferred retrieval
+    let response =
+        MNotifyRespInd::new(transaction_id, StatusField::Retrieved).encode()?;
+    post_pdu(&client, mmsc, response)
+        .context("Could not tell the MMSC the message was received")?;
+    Ok(())
+}
+
+fn forward(args: ForwardArgs) -> Result<()> {
+    let notification =
+        read_notification(&args.file).context("Notification File")?;
+    let notification = match Pdu::try_from(notification)
+        .context("Invalid message notification")?
+    {
+        Pdu::MNotificationInd(n) => n,
+        p => bail!("Expected a message notification, got {}", p.message_type()),
+    };
+
+    let mut request =
+        MForwardReq::new(notification.x_mms_content_location.clone());
+    if let Some(from) = &args.from {
+        request.from(FromField::Address(from.clone()));
+    }
+    for to in &args.to {
+        request.to(to.clone());
+    }
+    for cc in &args.cc {
+        request.cc(cc.clone());
+    }
+    for bcc in &args.bcc {
+        request.bcc(bcc.clone());
+    }
+    request.delivery_report(args.delivery_report);
+    request.read_report(args.read_report);
+
+    let client = http_client(args.netargs)?;
+    let response = post_pdu(&client, &args.mmsc, request.encode()?)
+        .context("Could not send forward request")?;
+    let response = parse_mms_pdu(&response)
+        .context("Could not parse response from the MMSC")?;
+
+    let conf = match Pdu::try_from(response)
+        .context("Invalid response from the MMSC")?
+    {
+        Pdu::MForwardConf(c) => c,
+        p => bail!("Expected a forward confirmation, got {}", p.message_type()),
+    };
+
+    if conf.x_mms_response_status != ResponseStatusField::Ok {
+        bail!(
+            "The MMSC didn't forward the message: {}{}",
+            conf.x_mms_response_status,
+            conf.x_mms_response_text
+                .map(|t| format!(", {}", t))
+                .unwrap_or_default()
+        );
+    }
+
+    println!("Message forwarded");
+    Ok(())
+}
+
+/// Read a notification, or a report sent the same way, from the wap push
+/// in `path`
+fn read_notification(path: &PathBuf) -> Result<VndWapMmsMessage<'static>> {
+    let data = read_file(path)?;
+
+    let parsed = parse_wap_push(&data).context("Failed to parse wap push")?;
+    let body = parsed
+        .parse_body()
+        .context("Failed to parse message notification")?;
+
+    Ok(body.into_owned())
+}
+
+fn http_client(args: NetArgs) -> Result<HttpClient> {
     let mut client = HttpClient::builder()
         .redirect_policy(isahc::config::RedirectPolicy::Follow);
 
-    if let Some(interface) = args.netargs.interface {
+    if let Some(interface) = args.interface {
         client =
             client.interface(isahc::config::NetworkInterface::name(interface));
     }
 
-    let proto = if args.netargs.ipv6 {
+    let proto = if args.ipv6 {
         isahc::config::IpVersion::V6
-    } else if args.netargs.ipv4 {
+    } else if args.ipv4 {
         isahc::config::IpVersion::V4
     } else {
         isahc::config::IpVersion::Any
     };
 
-    let client = client.ip_version(proto).build()?;
+    Ok(client.ip_version(proto).build()?)
+}
 
-    let response: Vec<u8> = {
-        let mut responce = client.get(message_url)?;
+/// Send a pdu to the MMSC, and return it's response
+fn post_pdu(client: &HttpClient, mmsc: &str, pdu: Vec<u8>) -> Result<Vec<u8>> {
+    let request = Request::post(mmsc)
+        .header("Content-Type", "application/vnd.wap.mms-message")
+        .body(pdu)?;
+    let mut response = client.send(request)?;
 
-        let mut buffer = Vec::new();
-        responce.body_mut().read_to_end(&mut buffer)?;
+    if !response.status().is_success() {
+        bail!("Received error from the MMSC: {:#?}", response);
+    }
 
-        let mut o = output.clone();
-        o.push("m-retrieve-conf.bin");
-        write_file(&o, &*buffer)
-            .context("Could not save response from server")?;
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body)?;
+    Ok(body)
+}
 
-        if !responce.status().is_success() {
-            bail!(
-                "Received error while trying to fetch message: {:#?}",
-                responce
-            );
-        }
+/// Copies everything read from `reader` to `copy`
+struct TeeReader<R, W> {
+    reader: R,
+    copy: W,
+}
 
-        buffer
-    };
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.copy.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
 
-    // TODO: Depends on https://github.com/Geal/nom/issues/1254
-    let (_remainder, parsed) = parse_mms_pdu(&*response).unwrap();
-    // .context("Could not parse response from server")?;
+fn write_file(path: &PathBuf, data: &[u8]) -> Result<()> {
+    let mut file = File::create(path).with_context(|| {
+        anyhow!("Could not create file to write: {:?}", path)
+    })?;
 
-    println!("Message Response Headers: {:#?}", parsed.headers);
+    file.write_all(data)
+        .with_context(|| anyhow!("Could not write data to file: {:?}", path))?;
 
-    save_body(&parsed, output)?;
     Ok(())
 }
 
-fn write_file(path: &PathBuf, data: &[u8]) -> Result<()> {
+fn copy_to_file(path: &Path, reader: &mut dyn Read) -> Result<()> {
     let mut file = File::create(path).with_context(|| {
         anyhow!("Could not create file to write: {:?}", path)
     })?;
 
-    file.write_all(data)
+    io::copy(reader, &mut file)
         .with_context(|| anyhow!("Could not write data to file: {:?}", path))?;
 
     Ok(())